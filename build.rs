@@ -1,5 +1,13 @@
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+#[path = "src/cron.rs"]
+mod cron;
+
 fn main() {
     linker_be_nice();
+    generate_config();
+    emit_build_info();
     println!("cargo:rustc-link-arg=-Tdefmt.x");
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
     println!("cargo:rustc-link-arg=-Tlinkall.x");
@@ -55,3 +63,410 @@ fn linker_be_nice() {
         std::env::current_exe().unwrap().display()
     );
 }
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// Values of `config.toml`. Every key can be overridden by the environment
+/// variable of the same name in upper case, e.g. `SSID` for `ssid`.
+struct ConfigSource(toml::Table);
+
+impl ConfigSource {
+    fn get(&self, key: &str) -> Option<toml::Value> {
+        let var = key.to_uppercase();
+        println!("cargo:rerun-if-env-changed={var}");
+        match std::env::var(&var) {
+            Ok(value) => Some(toml::Value::String(value)),
+            Err(_) => self.0.get(key).cloned(),
+        }
+    }
+
+    fn string(&self, key: &str) -> Option<String> {
+        self.get(key).map(|value| match value {
+            toml::Value::String(s) => s,
+            other => panic!("{CONFIG_FILE}: `{key}` must be a string, not {other}"),
+        })
+    }
+
+    fn required(&self, key: &str) -> String {
+        self.string(key).unwrap_or_else(|| {
+            panic!(
+                "set `{key}` in {CONFIG_FILE} or the {} environment variable",
+                key.to_uppercase()
+            )
+        })
+    }
+
+    fn number(&self, key: &str, default: i64) -> i64 {
+        self.optional_number(key).unwrap_or(default)
+    }
+
+    /// Like [`Self::number`], but the value must lie within `range`.
+    fn number_in(&self, key: &str, default: i64, range: RangeInclusive<i64>) -> i64 {
+        let n = self.number(key, default);
+        if !range.contains(&n) {
+            panic!(
+                "{CONFIG_FILE}: `{key}` must be {} to {}, not {n}",
+                range.start(),
+                range.end()
+            );
+        }
+        n
+    }
+
+    fn optional_number(&self, key: &str) -> Option<i64> {
+        match self.get(key)? {
+            toml::Value::Integer(n) => Some(n),
+            toml::Value::String(s) => {
+                Some(s.trim().parse().unwrap_or_else(|_| {
+                    panic!("{CONFIG_FILE}: `{key}` must be a number, not {s:?}")
+                }))
+            }
+            other => panic!("{CONFIG_FILE}: `{key}` must be a number, not {other}"),
+        }
+    }
+
+    /// An environment override must be `true`, `false`, `1` or `0`.
+    fn flag(&self, key: &str) -> bool {
+        match self.get(key) {
+            None => false,
+            Some(toml::Value::Boolean(b)) => b,
+            Some(toml::Value::String(s)) => match s.trim() {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                _ => panic!("{CONFIG_FILE}: `{key}` must be a boolean, not {s:?}"),
+            },
+            Some(other) => panic!("{CONFIG_FILE}: `{key}` must be a boolean, not {other}"),
+        }
+    }
+
+    /// A local time of day as `HH:MM`, returned as hour and minute.
+    fn time_of_day(&self, key: &str) -> Option<(u8, u8)> {
+        let value = self.string(key)?;
+        let time = value
+            .split_once(':')
+            .and_then(|(hour, minute)| Some((hour.parse::<u8>().ok()?, minute.parse::<u8>().ok()?)))
+            .filter(|&(hour, minute)| hour < 24 && minute < 60);
+        Some(time.unwrap_or_else(|| panic!("{CONFIG_FILE}: `{key}` must be HH:MM, not {value:?}")))
+    }
+
+    /// A TOML array of strings, or a comma-separated environment override.
+    fn list(&self, key: &str) -> Vec<String> {
+        match self.get(key) {
+            None => Vec::new(),
+            Some(toml::Value::String(s)) => s.split(',').map(|s| s.trim().to_string()).collect(),
+            Some(toml::Value::Array(items)) => items
+                .into_iter()
+                .map(|item| match item {
+                    toml::Value::String(s) => s,
+                    other => panic!("{CONFIG_FILE}: `{key}` must list strings, not {other}"),
+                })
+                .collect(),
+            Some(other) => panic!("{CONFIG_FILE}: `{key}` must be a list, not {other}"),
+        }
+    }
+}
+
+/// `value` of `key` as an `Ipv4Address` expression.
+fn ipv4_address(key: &str, value: &str) -> String {
+    let address: std::net::Ipv4Addr = value.trim().parse().unwrap_or_else(|_| {
+        panic!("{CONFIG_FILE}: `{key}` must be an IPv4 address, not {value:?}")
+    });
+    let [a, b, c, d] = address.octets();
+    format!("Ipv4Address::new({a}, {b}, {c}, {d})")
+}
+
+/// Whether `host` is a valid DNS name.
+fn is_hostname(host: &str) -> bool {
+    host.split('.').all(|label| {
+        !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
+/// Writes `CONFIG` for `src/config.rs` to `$OUT_DIR/config.rs`.
+fn generate_config() {
+    println!("cargo:rerun-if-changed={CONFIG_FILE}");
+    let table = match std::fs::read_to_string(CONFIG_FILE) {
+        Ok(text) => text
+            .parse()
+            .unwrap_or_else(|e| panic!("{CONFIG_FILE} is not valid TOML: {e}")),
+        Err(_) => toml::Table::new(),
+    };
+    let source = ConfigSource(table);
+
+    let mut out = String::from("pub const CONFIG: Config = Config {\n");
+    let mut field = |name: &str, value: String| writeln!(out, "    {name}: {value},").unwrap();
+    field("ssid", format!("{:?}", source.required("ssid")));
+    field("password", format!("{:?}", source.required("password")));
+    let wifi_bssid = match source.string("wifi_bssid") {
+        None => String::from("BssidPin::Any"),
+        Some(value) if value == "strongest" => String::from("BssidPin::Strongest"),
+        Some(value) => {
+            let bytes: Vec<u8> = value
+                .split(':')
+                .map(|byte| u8::from_str_radix(byte, 16))
+                .collect::<Result<_, _>>()
+                .ok()
+                .filter(|bytes: &Vec<u8>| bytes.len() == 6)
+                .unwrap_or_else(|| {
+                    panic!(
+                        "{CONFIG_FILE}: `wifi_bssid` must be a MAC address like \
+                         aa:bb:cc:dd:ee:ff or \"strongest\", not {value:?}"
+                    )
+                });
+            format!("BssidPin::Fixed({bytes:?})")
+        }
+    };
+    field("wifi_bssid", wifi_bssid);
+    let wifi_channel = source.optional_number("wifi_channel");
+    if let Some(channel) = wifi_channel {
+        assert!(
+            (1..=13).contains(&channel),
+            "{CONFIG_FILE}: `wifi_channel` must be 1 to 13, not {channel}"
+        );
+    }
+    field("wifi_channel", format!("{wifi_channel:?}"));
+    field(
+        "calendar_url",
+        format!(
+            "{:?}",
+            source
+                .string("calendar_url")
+                .unwrap_or_else(|| String::from(
+                    "https://backend.stadtreinigung.hamburg/kalender/abholtermine.ics?hnIds=44353"
+                ))
+        ),
+    );
+    let syslog_host = source
+        .string("syslog_host")
+        .map(|host| ipv4_address("syslog_host", &host));
+    field(
+        "syslog_host",
+        syslog_host.map_or_else(|| String::from("None"), |host| format!("Some({host})")),
+    );
+    let ntp_server = source
+        .string("ntp_server")
+        .unwrap_or_else(|| String::from("pool.ntp.org"));
+    let ntp_server = if ntp_server.parse::<std::net::Ipv4Addr>().is_ok() {
+        format!(
+            "NtpServer::Address({})",
+            ipv4_address("ntp_server", &ntp_server)
+        )
+    } else if is_hostname(&ntp_server) {
+        format!("NtpServer::Host({ntp_server:?})")
+    } else {
+        panic!("{CONFIG_FILE}: `ntp_server` must be a hostname or IPv4 address, not {ntp_server:?}")
+    };
+    field("ntp_server", ntp_server);
+    field(
+        "diagnostics_url",
+        format!("{:?}", source.string("diagnostics_url")),
+    );
+    field(
+        "diagnostics_after_hours",
+        source
+            .number_in("diagnostics_after_hours", 48, 1..=24 * 365)
+            .to_string(),
+    );
+    let dns_servers: Vec<String> = source
+        .list("dns_servers")
+        .iter()
+        .map(|server| ipv4_address("dns_servers", server))
+        .collect();
+    field("dns_servers", format!("&[{}]", dns_servers.join(", ")));
+    let reminder_language = match source.string("reminder_language").as_deref() {
+        None | Some("de") => "Language::De",
+        Some("en") => "Language::En",
+        Some(other) => panic!("{CONFIG_FILE}: `reminder_language` must be de or en, not {other:?}"),
+    };
+    field("reminder_language", String::from(reminder_language));
+    field(
+        "reminder_template",
+        format!("{:?}", source.string("reminder_template")),
+    );
+    let holiday_region = source.string("holiday_region").map(|code| {
+        let region = match code.as_str() {
+            "BW" => "BadenWuerttemberg",
+            "BY" => "Bayern",
+            "BE" => "Berlin",
+            "BB" => "Brandenburg",
+            "HB" => "Bremen",
+            "HH" => "Hamburg",
+            "HE" => "Hessen",
+            "MV" => "MecklenburgVorpommern",
+            "NI" => "Niedersachsen",
+            "NW" => "NordrheinWestfalen",
+            "RP" => "RheinlandPfalz",
+            "SL" => "Saarland",
+            "SN" => "Sachsen",
+            "ST" => "SachsenAnhalt",
+            "SH" => "SchleswigHolstein",
+            "TH" => "Thueringen",
+            _ => panic!(
+                "{CONFIG_FILE}: `holiday_region` must be a German state code like \"HH\", \
+                 not {code:?}"
+            ),
+        };
+        format!("Some(Region::{region})")
+    });
+    field(
+        "holiday_region",
+        holiday_region.unwrap_or_else(|| String::from("None")),
+    );
+    let (hour, minute) = source.time_of_day("reminder_time").unwrap_or((18, 0));
+    field("reminder_hour", hour.to_string());
+    field("reminder_minute", minute.to_string());
+    let refresh_schedule = source.string("refresh_schedule").map(|spec| {
+        let fields = cron::parse(&spec).unwrap_or_else(|e| {
+            panic!(
+                "{CONFIG_FILE}: `refresh_schedule` must be a cron spec like \"0 6 * * 1-5\", \
+                 not {spec:?}: {e}"
+            )
+        });
+        format!("Some(Schedule::new({spec:?}, cron::{fields:?}))")
+    });
+    field(
+        "refresh_schedule",
+        refresh_schedule.unwrap_or_else(|| String::from("None")),
+    );
+    field(
+        "collection_day_time",
+        format!("{:?}", source.time_of_day("collection_day_time")),
+    );
+    field(
+        "event_horizon_weeks",
+        source
+            .number_in("event_horizon_weeks", 8, 1..=520)
+            .to_string(),
+    );
+    field(
+        "max_events",
+        source.number_in("max_events", 64, 1..=1024).to_string(),
+    );
+    field(
+        "stale_after_days",
+        source.number_in("stale_after_days", 3, 1..=365).to_string(),
+    );
+    field(
+        "monthly_traffic_budget_kb",
+        source
+            .number_in("monthly_traffic_budget_kb", 0, 0..=i64::MAX)
+            .to_string(),
+    );
+    let night_light = match (
+        source.time_of_day("night_light_start"),
+        source.time_of_day("night_light_end"),
+    ) {
+        (Some((start_hour, start_minute)), Some((end_hour, end_minute))) => format!(
+            "Some(NightLight {{ start_hour: {start_hour}, start_minute: {start_minute}, \
+             end_hour: {end_hour}, end_minute: {end_minute}, brightness: {} }})",
+            source.number_in("night_light_brightness", 10, 0..=255)
+        ),
+        (None, None) => String::from("None"),
+        _ => panic!("{CONFIG_FILE}: set both `night_light_start` and `night_light_end`"),
+    };
+    field("night_light", night_light);
+    let flag_servo = if source.flag("flag_servo") {
+        format!(
+            "Some(FlagServo {{ raised_angle: {}, lowered_angle: {} }})",
+            source.number_in("flag_raised_angle", 90, 0..=180),
+            source.number_in("flag_lowered_angle", 0, 0..=180)
+        )
+    } else {
+        String::from("None")
+    };
+    field("flag_servo", flag_servo);
+    field("door_sensor", source.flag("door_sensor").to_string());
+    field(
+        "watchdog_failures",
+        source
+            .number_in("watchdog_failures", 2, 0..=1000)
+            .to_string(),
+    );
+    field("test_reminder", source.flag("test_reminder").to_string());
+    field(
+        "debug_start_time",
+        format!("{:?}", source.optional_number("debug_start_time")),
+    );
+    field(
+        "debug_clock_speedup",
+        source
+            .number_in("debug_clock_speedup", 1, 1..=10_000)
+            .to_string(),
+    );
+    field("power_profile", source.flag("power_profile").to_string());
+    // The heap lives in .dram2_uninit, which holds 98 767 bytes on the ESP32.
+    field(
+        "heap_size",
+        source
+            .number_in("heap_size", 98767, 16_384..=98_767)
+            .to_string(),
+    );
+    // A TLS record takes up to 16 640 bytes.
+    field(
+        "rx_buffer_size",
+        source
+            .number_in("rx_buffer_size", 32000, 16_640..=65_536)
+            .to_string(),
+    );
+    out.push_str("};\n");
+
+    let path = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("config.rs");
+    std::fs::write(path, out).unwrap();
+}
+
+/// Sets `GIT_HASH`, `BUILD_TIME` and `ENABLED_FEATURES` for the `version`
+/// module.
+fn emit_build_info() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let git_hash = std::process::Command::new("git")
+        .args(["describe", "--always", "--dirty", "--abbrev=8"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map_or_else(|| String::from("unknown"), |hash| hash.trim().to_string());
+    println!("cargo:rustc-env=GIT_HASH={git_hash}");
+
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible.
+    let unix_time = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        });
+    println!("cargo:rustc-env=BUILD_TIME={}", format_utc(unix_time));
+
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(var, _)| var.strip_prefix("CARGO_FEATURE_").map(str::to_lowercase))
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=ENABLED_FEATURES={}", features.join(","));
+}
+
+/// Formats `unix_time` as `YYYY-MM-DDTHH:MM:SSZ`.
+fn format_utc(unix_time: u64) -> String {
+    let (days, secs) = (unix_time / 86_400, unix_time % 86_400);
+    // Civil date from days since 1970-01-01, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
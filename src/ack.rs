@@ -0,0 +1,51 @@
+//! Acknowledged collections.
+//!
+//! Collections are identified by [`EventId`], which stays the same when the
+//! calendar is fetched again. Passes skip acknowledged collections, so neither
+//! a refresh nor the collection day pass re-arms a reminder that was already
+//! dealt with.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use time::Date;
+
+use crate::clock;
+use crate::ics::EventId;
+use crate::status_led;
+use crate::syslog::{self, AuditEvent};
+
+/// Collections of the reminder or collection day indicator on display.
+static SHOWN: Mutex<CriticalSectionRawMutex, RefCell<Vec<EventId>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+static ACKNOWLEDGED: Mutex<CriticalSectionRawMutex, RefCell<Vec<EventId>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+/// Records the collections of the indicator just set.
+pub fn shown(ids: Vec<EventId>) {
+    SHOWN.lock(|shown| *shown.borrow_mut() = ids);
+}
+
+/// Ends the indicator on display, if any, remembers its collections as
+/// acknowledged and audits it with `source`. Returns whether there was one.
+pub fn acknowledge(source: &str) -> bool {
+    if !status_led::acknowledge() {
+        return false;
+    }
+    let shown = SHOWN.lock(|shown| core::mem::take(&mut *shown.borrow_mut()));
+    ACKNOWLEDGED.lock(|acknowledged| acknowledged.borrow_mut().extend(shown));
+    let unix_time = clock::shared().map(|clock| clock.now());
+    syslog::audit(unix_time, &AuditEvent::Acknowledged { source });
+    true
+}
+
+pub fn is_acknowledged(id: EventId) -> bool {
+    ACKNOWLEDGED.lock(|acknowledged| acknowledged.borrow().contains(&id))
+}
+
+/// Forgets acknowledgements of collections before `today`.
+pub fn prune(today: Date) {
+    ACKNOWLEDGED.lock(|acknowledged| acknowledged.borrow_mut().retain(|id| id.date >= today));
+}
@@ -8,43 +8,91 @@
 
 use embassy_net::{IpEndpoint, Ipv4Address};
 
+use core::net::IpAddr;
+
+use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 use defmt::info;
 use embassy_executor::Spawner;
+use embassy_futures::select::{Either, Either3, select, select3};
 use embassy_net::udp::{UdpMetadata, UdpSocket};
 use embassy_net::{
     DhcpConfig, Runner, Stack, StackResources,
-    dns::DnsSocket,
     tcp::client::{TcpClient, TcpClientState},
 };
-use embassy_time::{Duration, Timer};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use embedded_nal_async::{AddrType, Dns};
 use esp_hal::clock::CpuClock;
-use esp_hal::rng::Rng;
+use esp_hal::gpio::{Input, InputConfig, Level, Output, OutputConfig, Pull};
+use esp_hal::interrupt::Priority;
+use esp_hal::interrupt::software::SoftwareInterruptControl;
+use esp_hal::peripherals::{GPIO13, LEDC};
+use esp_hal::system::software_reset;
 use esp_hal::timer::timg::TimerGroup;
 use esp_println::{self as _, println};
 use esp_radio::wifi::{
     ClientConfig, ModeConfig, ScanConfig, WifiController, WifiDevice, WifiEvent, WifiStaState,
 };
+use esp_rtos::embassy::InterruptExecutor;
 
 use esp_hal::{ rmt::Rmt, time::Rate};
 use esp_hal_smartled::SmartLedsAdapter;
-use smart_leds::{SmartLedsWrite as _, brightness, colors::RED};
 
-use reqwless::client::{HttpClient, TlsConfig};
+use reqwless::client::HttpClient;
 use smoltcp::storage::PacketMetadata;
-use time::{Date, Month, UtcDateTime};
+use time::Date;
+use wifi_async_http::ack;
+use wifi_async_http::boot;
+use wifi_async_http::clock::{self, WallClock};
+use wifi_async_http::config::{BssidPin, CONFIG, FlagServo, NightLight, NtpServer};
+use wifi_async_http::device;
+use wifi_async_http::diagnostics;
+use wifi_async_http::dns::Resolver;
+use wifi_async_http::entropy;
+use wifi_async_http::event::Event;
+use wifi_async_http::fetch::{self, FetchError, HttpFetch, ReqwlessFetch};
+use wifi_async_http::flag;
+use wifi_async_http::heap;
+use wifi_async_http::holidays;
+use wifi_async_http::ics::{self, EventId, EventWindow, IcsEvent, extract_ics_event};
+use wifi_async_http::netstats::{self, Counter};
+use wifi_async_http::ping;
+use wifi_async_http::profile::{self, Phase};
+use wifi_async_http::reminder;
+use wifi_async_http::scan::{self, AccessPoint};
+use wifi_async_http::schedule::Schedule;
+use wifi_async_http::status_led::{self, SystemState};
+use wifi_async_http::syslog::{self, AuditEvent, audit};
+use wifi_async_http::text::{Overflow, Text};
+use wifi_async_http::traffic::Traffic;
+use wifi_async_http::upcoming;
 
 #[panic_handler]
-fn panic(_: &core::panic::PanicInfo) -> ! {
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    // Also reports failed allocations, with the requested size.
+    println!("{}", info);
     loop {}
 }
 
-const NTP_SERVER: Ipv4Address = Ipv4Address::new(129, 6, 15, 28); // time.nist.gov
 const NTP_PORT: u16 = 123;
 const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+const NTP_TIMEOUT: Duration = Duration::from_secs(5);
 
-static RX_BUFFER_SIZE: usize = 32000;
+const RX_BUFFER_SIZE: usize = CONFIG.rx_buffer_size;
+const LED_BRIGHTNESS: u8 = 100;
+const NET_STATS_INTERVAL: Duration = Duration::from_secs(3600);
+const BUTTON_DEBOUNCE: Duration = Duration::from_millis(50);
+/// Longer than for the button, a swinging door can bounce the magnet past
+/// the reed switch a few times.
+const DOOR_DEBOUNCE: Duration = Duration::from_millis(500);
+/// Asks the connection task to stop and restart the WiFi controller.
+static RADIO_RESTART: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+/// Pinged when no DNS servers are configured: Quad9 answers echo requests.
+const PING_INTERNET_HOST: Ipv4Address = Ipv4Address::new(9, 9, 9, 9);
+const CONNECTIVITY_CHECK_URL: &str = "http://connectivitycheck.gstatic.com/generate_204";
 extern crate alloc;
 
 // This creates a default app-descriptor required by the esp-idf bootloader.
@@ -61,103 +109,48 @@ macro_rules! mk_static {
     }};
 }
 
-const SSID: &str = env!("SSID");
-const PASSWORD: &str = env!("PASSWORD");
-
-#[derive(defmt::Format, Copy, Clone, Debug)]
-#[repr(u8)]
-enum Event {
-    Verpackungs,
-    Bio,
-    Papier,
-    Restmüll,
-    Laubsack,
-    Weihnachtsbäume,
-}
-#[derive(Debug)]
-struct IcsEvent {
-    dtstart: Option<Date>,
-    event_type: Option<Event>,
-}
-
-fn parse_yyyymmdd(s: &str) -> Result<Date, &'static str> {
-    if s.len() != 8 {
-        return Err("Expected 8 characters (YYYYMMDD)");
-    }
-
-    let year = s[0..4].parse::<i32>().map_err(|_| "Invalid year")?;
-    let month_num = s[4..6].parse::<u8>().map_err(|_| "Invalid month")?;
-    let day = s[6..8].parse::<u8>().map_err(|_| "Invalid day")?;
-
-    let month = Month::try_from(month_num).expect("month must be in 1..=12");
+const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+/// Minimum time between two passes of the same kind, so a pass that starts
+/// slightly early isn't followed by a second one at the same time.
+const MIN_PASS_GAP: i64 = 3600;
+/// Fetch interval once the monthly traffic budget is used up. Shorter than
+/// the default staleness threshold, so the cached dates don't turn stale.
+/// Passes are timed by their start and the fetch ends a little later, so
+/// the interval is one pass gap short of two days.
+const BUDGET_REFRESH_INTERVAL: Duration = Duration::from_secs(2 * 24 * 3600 - MIN_PASS_GAP as u64);
+/// Room for the short labels of all bins due on one day.
+const MAX_AUDIT_NAMES_LEN: usize = 64;
+/// Fetch errors kept for the diagnostic bundle.
+const MAX_RECENT_ERRORS: usize = 4;
+const CLOCK_RETRY_INTERVAL: Duration = Duration::from_secs(300);
+const SECONDS_PER_DAY: i64 = 86_400;
+/// The debug clock options replace SNTP time, so resyncs are skipped.
+const DEBUG_CLOCK: bool = CONFIG.debug_start_time.is_some() || CONFIG.debug_clock_speedup != 1;
+/// How long the test reminder is shown before the first fetch.
+const TEST_REMINDER_DURATION: Duration = Duration::from_secs(10);
 
-    Date::from_calendar_date(year, month, day).map_err(|_| "Invalid date")
-}
-
-fn extract_ics_event(ics_document: String) -> Vec<IcsEvent> {
-    let mut ics_events: Vec<IcsEvent> = Vec::new();
-    let mut event_type: Option<Event> = None;
-    let mut start_ts: Option<Date> = None;
-
-    for line_str in ics_document.lines() {
-        let line = line_str.trim_end();
-
-        if line.starts_with("DTSTART;") {
-            assert!(line.starts_with("DTSTART;TZID=Europe/Berlin;VALUE=DATE:"),);
-            assert!(line.len() == 46, "Line length: {}", line.len());
-            start_ts = Some(parse_yyyymmdd(&line[38..]).unwrap());
-        } else if line.starts_with("SUMMARY:") {
-            let event_name = line[8..].trim();
-            match event_name {
-                "Abfuhr gelbe Wertstofftonne/-sack" => {
-                    event_type = Some(Event::Verpackungs);
-                }
-                "Abfuhr grüne Biotonne" => {
-                    event_type = Some(Event::Bio);
-                }
-                "Abfuhr blaue Papiertonne" => {
-                    event_type = Some(Event::Papier);
-                }
-                "Abfuhr schwarze Restmülltonne" => {
-                    event_type = Some(Event::Restmüll);
-                }
-                "Abfuhr Laubsäcke" => {
-                    event_type = Some(Event::Laubsack);
-                }
-                "Abfuhr Weihnachtsbäume" => {
-                    event_type = Some(Event::Weihnachtsbäume);
-                }
-                _ => {
-                    println!("Unknown Event: {}", line); // Placeholder
-                }
-            }
-        } else if line == "END:VEVENT" {
-            assert!(start_ts.is_some());
-            assert!(event_type.is_some());
-            //println!("{:?} @ {:?}", event_type.unwrap(), start_ts.unwrap());
-            ics_events.push(IcsEvent {
-                dtstart: start_ts,
-                event_type: event_type,
-            });
-        }
-    }
-    return ics_events;
-}
+pub async fn ntp_request(socket: &mut UdpSocket<'_>, dns: &Resolver<'_>) -> Result<i64, ()> {
+    let server = match CONFIG.ntp_server {
+        NtpServer::Address(address) => address,
+        NtpServer::Host(host) => match dns.get_host_by_name(host, AddrType::IPv4).await {
+            Ok(IpAddr::V4(address)) => address,
+            _ => return Err(()),
+        },
+    };
 
-pub async fn ntp_request(socket: &mut UdpSocket<'_>) -> Result<i64, ()> {
     let mut request = [0u8; 48];
     request[0] = 0x23; // LI=0, VN=4, Mode=3 (client)
 
-    let endpoint = IpEndpoint::new(NTP_SERVER.into(), NTP_PORT);
+    let endpoint = IpEndpoint::new(server.into(), NTP_PORT);
 
     socket.send_to(&request, endpoint).await.map_err(|_| ())?;
 
     let mut response = [0u8; 48];
 
-    // Optional timeout
-    Timer::after(Duration::from_secs(5)).await;
-
-    let (_len, _src) = socket.recv_from(&mut response).await.map_err(|_| ())?;
+    let (_len, _src) = with_timeout(NTP_TIMEOUT, socket.recv_from(&mut response))
+        .await
+        .map_err(|_| ())?
+        .map_err(|_| ())?;
 
     // Transmit Timestamp starts at byte 40
     let seconds =
@@ -168,6 +161,13 @@ pub async fn ntp_request(socket: &mut UdpSocket<'_>) -> Result<i64, ()> {
     Ok(unix_time as i64)
 }
 
+// Task priorities: the thread mode executor runs the network, the daily pass
+// and the status LED. TLS handshakes and ICS parsing keep it busy for up to
+// seconds at a time without yielding. The button, the door sensor and the
+// flag servo therefore run on an interrupt executor at a higher priority,
+// which preempts thread mode. An acknowledgement then takes effect within
+// the debounce time, even in the middle of a fetch. The LED catches up once
+// thread mode yields again.
 #[esp_rtos::main]
 async fn main(spawner: Spawner) -> ! {
     // generator version: 1.0.0
@@ -175,10 +175,16 @@ async fn main(spawner: Spawner) -> ! {
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
     let peripherals = esp_hal::init(config);
 
-    esp_alloc::heap_allocator!(#[unsafe(link_section = ".dram2_uninit")] size: 98767);
+    esp_alloc::heap_allocator!(#[unsafe(link_section = ".dram2_uninit")] size: CONFIG.heap_size);
 
     let timg0 = TimerGroup::new(peripherals.TIMG0);
     esp_rtos::start(timg0.timer0);
+    let software_interrupts = SoftwareInterruptControl::new(peripherals.SW_INTERRUPT);
+    let high_priority = mk_static!(
+        InterruptExecutor<2>,
+        InterruptExecutor::new(software_interrupts.software_interrupt2)
+    )
+    .start(Priority::Priority2);
 
     info!("Embassy initialized!");
 
@@ -188,10 +194,18 @@ async fn main(spawner: Spawner) -> ! {
         let rmt = Rmt::new(peripherals.RMT, frequency).expect("Failed to initialize RMT0");
         SmartLedsAdapter::new(rmt.channel0, peripherals.GPIO2, &mut led_buffer)
     };
-    let level = 100;
-    led.write(brightness([RED].into_iter(), level)).unwrap();
     info!("LED abstraction layer is initialized sucessfully.");
 
+    if CONFIG.power_profile {
+        profile::install(Output::new(
+            peripherals.GPIO4,
+            Level::Low,
+            OutputConfig::default(),
+        ));
+        info!("Power profiling enabled, phase marker on GPIO4");
+    }
+
+    heap::checkpoint("before WiFi init");
     // let radio_init = esp_radio::init().expect("Failed to initialize Wi-Fi/BLE controller");
     let radio_init = &*mk_static!(
         esp_radio::Controller<'static>,
@@ -203,30 +217,69 @@ async fn main(spawner: Spawner) -> ! {
             .expect("Failed to initialize Wi-Fi controller");
 
     let wifi_interface = interfaces.sta;
+    device::init(wifi_interface.mac_address());
+    boot::log(&format!("{:?}", wifi_controller.capabilities()));
+    heap::checkpoint("after WiFi init");
 
-    let rng = Rng::new();
-    let net_seed = rng.random() as u64 | ((rng.random() as u64) << 32);
-    let tls_seed = rng.random() as u64 | ((rng.random() as u64) << 32);
+    let net_seed = entropy::seed();
 
-    let dhcp_config = DhcpConfig::default();
+    let mut dhcp_config = DhcpConfig::default();
+    dhcp_config.hostname = device::hostname().as_str().try_into().ok();
     let config = embassy_net::Config::dhcpv4(dhcp_config);
 
     // Init network stack
     let (stack, runner) = embassy_net::new(
         wifi_interface,
         config,
-        mk_static!(StackResources<3>, StackResources::<3>::new()),
+        mk_static!(StackResources<5>, StackResources::<5>::new()),
         net_seed,
     );
 
     spawner.spawn(connection(wifi_controller)).ok();
     spawner.spawn(net_task(runner)).ok();
+    spawner.spawn(net_monitor(stack)).ok();
+    // The BOOT button of the devkit.
+    let button = Input::new(
+        peripherals.GPIO0,
+        InputConfig::default().with_pull(Pull::Up),
+    );
+    high_priority.spawn(button_task(button)).ok();
+    if CONFIG.door_sensor {
+        let door = Input::new(
+            peripherals.GPIO14,
+            InputConfig::default().with_pull(Pull::Up),
+        );
+        high_priority.spawn(door_sensor(door)).ok();
+    }
+    if let Some(servo) = CONFIG.flag_servo {
+        high_priority
+            .spawn(flag_servo(peripherals.LEDC, peripherals.GPIO13, servo))
+            .ok();
+    }
+    if let Some(schedule) = CONFIG.night_light {
+        spawner.spawn(night_light(schedule)).ok();
+    }
 
-    wait_for_connection(stack).await;
+    let app = run(stack);
+    let status = status_led::run(&mut led, LED_BRIGHTNESS);
+    let audit_sender = async {
+        match CONFIG.syslog_host {
+            Some(host) => {
+                syslog::run(stack, IpEndpoint::new(host.into(), syslog::SYSLOG_PORT)).await
+            }
+            None => core::future::pending().await,
+        }
+    };
+    // Each output runs on its own, so a slow one can't hold up the others
+    // or the daily pass.
+    match select3(app, status, audit_sender).await {
+        Either3::First(never) | Either3::Second(never) | Either3::Third(never) => never,
+    }
+}
 
-    let s: String = get_ics(stack, tls_seed).await;
-    let events = extract_ics_event(s);
-    info!("Extracted {} events", events.len());
+async fn run(stack: Stack<'static>) -> ! {
+    wait_for_connection(stack).await;
+    status_led::set_state(SystemState::TimeSync);
 
     //How many packets can be buffered
     const RX_PACKET_COUNT: usize = 1;
@@ -238,36 +291,487 @@ async fn main(spawner: Spawner) -> ! {
 
     // Payload buffers
     let mut rx_buf = [0u8; 256];
-    let mut tx_buf = [0u8; 256];
+    let mut tx_buf = [0u8; 512];
 
     let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buf, &mut tx_meta, &mut tx_buf);
     socket.bind(0).unwrap(); // random local port
+    let dns = Resolver::new(stack, CONFIG.dns_servers);
 
-    let unix_time = ntp_request(&mut socket).await.unwrap();
-    info!("Got Unix timestamp: {}", unix_time);
-    let today = UtcDateTime::from_unix_timestamp(unix_time).unwrap().date();
-    info!(
-        "Converted Unix timestamp to Date: {}-{}-{}",
-        today.day() as u16,
-        today.month() as u16,
-        today.year() as u16
-    );
+    // Without a clock the device still fetches and shows the dates, but
+    // reminders are suppressed instead of firing on the wrong day.
+    let mut clock = match CONFIG.debug_start_time {
+        Some(unix_time) => Some(WallClock::new(unix_time)),
+        None => match ntp_request(&mut socket, &dns).await {
+            Ok(unix_time) => Some(WallClock::new(unix_time)),
+            Err(()) => {
+                println!("SNTP sync failed, clock not set");
+                None
+            }
+        },
+    }
+    .map(|clock| clock.with_speedup(CONFIG.debug_clock_speedup));
+    if DEBUG_CLOCK {
+        println!(
+            "Debug clock at {}x speed, SNTP resyncs disabled",
+            CONFIG.debug_clock_speedup
+        );
+    }
 
-    for event in events {
-        info!(
-            "checking {} at {}-{}-{} ",
-            event.event_type,
-            event.dtstart.unwrap().day() as u16,
-            event.dtstart.unwrap().month() as u16,
-            event.dtstart.unwrap().year() as u16,
+    let language = CONFIG.reminder_language;
+    let reminder_template = CONFIG
+        .reminder_template
+        .unwrap_or(language.default_template());
+    let holiday_region = CONFIG.holiday_region;
+    let stale_after_days = CONFIG.stale_after_days;
+    let refresh_schedule = CONFIG.refresh_schedule;
+
+    let mut event_window = EventWindow {
+        today: None,
+        horizon_days: CONFIG.event_horizon_weeks * 7,
+        max_events: CONFIG.max_events,
+    };
+
+    let dns_servers = CONFIG.dns_servers;
+
+    if CONFIG.test_reminder {
+        // Same outputs as a real reminder, so the LED and the syslog setup
+        // can be checked without waiting for a collection day.
+        let bin = Event::Restmüll;
+        let text = reminder::render(reminder_template, language, &[bin]);
+        info!("Test reminder: {}", text.as_str());
+        status_led::set_reminder(&[bin.style().color]);
+        audit(
+            clock.map(|clock| clock.now()),
+            &AuditEvent::TestReminder {
+                event: bin.style().short_label,
+            },
         );
+        Timer::after(TEST_REMINDER_DURATION).await;
+    }
+
+    // Events of the last successful fetch. They keep the reminders working
+    // while the backend is unreachable.
+    let mut events: Vec<IcsEvent> = Vec::new();
+    let mut last_fetch: Option<Instant> = None;
+    // The body is parsed in place from this buffer, without a copy.
+    let mut body_buffer = [0u8; RX_BUFFER_SIZE];
+    let mut fetcher = ReqwlessFetch::new(stack, dns_servers);
+    let mut traffic = Traffic::default();
+    // Unknown summaries already sent to the collector, so each one is only
+    // reported once per boot instead of after every fetch.
+    let mut reported_summaries: Vec<String> = Vec::new();
+    let mut offline_failures = 0;
+    let mut recent_errors: Vec<String> = Vec::new();
 
-        if today.next_day().eq(&event.dtstart) {
-            info!("Tomorrow is {}", event.event_type)
+    let mut pass = Pass::Reminder;
+    let mut pass_times = PassTimes::default();
+    loop {
+        if let Some(wall_clock) = clock {
+            clock::publish(wall_clock);
+        }
+        let unix_time = clock.map(|clock| clock.now());
+        let today = clock.map(|clock| clock.today());
+        if let Some(today) = today {
+            ack::prune(today);
+        }
+
+        // The morning pass only switches the indicator, from the cached
+        // events.
+        if pass == Pass::CollectionDay
+            && let Some(today) = today
+        {
+            let bins = unacknowledged_bins(&events, today);
+            if !bins.is_empty() {
+                for bin in &bins {
+                    info!("Collection today: {}", bin);
+                }
+                let colors: Vec<_> = bins.iter().map(|bin| bin.style().color).collect();
+                status_led::set_collection_today(&colors);
+                ack::shown(event_ids(today, &bins));
+            } else {
+                // Ends last night's reminder if nothing is collected today.
+                status_led::acknowledge();
+            }
+            pass = wait_for_next_cycle(
+                &mut socket,
+                &dns,
+                &mut clock,
+                &mut pass_times,
+                refresh_schedule,
+            )
+            .await;
+            continue;
+        }
+        match today {
+            Some(today) => info!(
+                "Today is {}-{}-{}",
+                today.day() as u16,
+                today.month() as u16,
+                today.year() as u16
+            ),
+            None => info!("Clock not set, reminders are suppressed"),
+        }
+        event_window.today = today;
+
+        if let Some(today) = today {
+            traffic.roll(today);
+        }
+        let over_budget = CONFIG.monthly_traffic_budget_kb > 0
+            && traffic.month() >= CONFIG.monthly_traffic_budget_kb * 1024;
+        let fetch_due = !over_budget
+            || last_fetch.is_none_or(|fetched_at| fetched_at.elapsed() >= BUDGET_REFRESH_INTERVAL);
+        let fetched = if fetch_due {
+            Some(
+                fetcher
+                    .get_text(CONFIG.calendar_url, &mut body_buffer)
+                    .await,
+            )
+        } else {
+            println!("Monthly traffic budget used up, keeping the cached dates");
+            None
+        };
+
+        let parsed = fetched.map(|fetched| {
+            let s = fetched?;
+            traffic.add(s.len());
+            info!(
+                "Downloaded {} bytes today, {} this month",
+                traffic.today(),
+                traffic.month()
+            );
+            heap::checkpoint("before parsing");
+            let parse = profile::begin(Phase::Parse);
+            let (fetched, report) = extract_ics_event(s, &event_window);
+            drop(parse);
+            heap::checkpoint("after parsing");
+            report.log();
+            if report.vevents == 0 {
+                return Err(FetchError::NoEvents);
+            }
+            Ok((fetched, report))
+        });
+
+        match parsed {
+            // Yesterday's reminder must not outlive the skipped fetch; the
+            // reminder below is shown again from the cached dates if due.
+            None if pass != Pass::Refresh => {
+                status_led::acknowledge();
+            }
+            None => {}
+            Some(Ok((fetched, report))) => {
+                offline_failures = 0;
+                recent_errors.clear();
+                events = fetched;
+                last_fetch = Some(Instant::now());
+                set_fetch_state(pass, SystemState::Healthy);
+                audit(
+                    unix_time,
+                    &AuditEvent::FetchOk {
+                        events: report.events,
+                        dropped: report.dropped_events,
+                    },
+                );
+                for summary in report.unknown_summary_samples {
+                    if reported_summaries.contains(&summary) {
+                        continue;
+                    }
+                    audit(unix_time, &AuditEvent::UnknownSummary { summary: &summary });
+                    reported_summaries.push(summary);
+                }
+            }
+            Some(Err(e)) => {
+                println!("Failed to fetch calendar: {:?}", e);
+                if matches!(
+                    e,
+                    FetchError::Http(reqwless::Error::Network(_) | reqwless::Error::Dns)
+                ) {
+                    netstats::increment(Counter::SocketError);
+                }
+                let connectivity = if calendar_on_lan() {
+                    probe_gateway(stack).await
+                } else {
+                    probe_connectivity(stack, dns_servers).await
+                };
+                if connectivity == Connectivity::Offline {
+                    offline_failures += 1;
+                    connectivity_watchdog(stack, offline_failures).await;
+                }
+                let mut reason = match connectivity {
+                    Connectivity::CaptivePortal => {
+                        println!("Captive portal detected, sign in to the network first");
+                        set_fetch_state(pass, SystemState::CaptivePortal);
+                        String::from("captive portal detected")
+                    }
+                    Connectivity::Online | Connectivity::Offline => {
+                        set_fetch_state(pass, SystemState::FetchFailed);
+                        match e {
+                            FetchError::NoEvents => String::from("no events in calendar"),
+                            _ => format!("{:?}", e),
+                        }
+                    }
+                };
+                let pings = ping_report(stack, dns_servers).await;
+                println!("{}", pings.as_str());
+                reason.push_str("; ");
+                reason.push_str(&pings);
+                audit(unix_time, &AuditEvent::FetchFailed { reason: &reason });
+                if recent_errors.len() == MAX_RECENT_ERRORS {
+                    recent_errors.remove(0);
+                }
+                recent_errors.push(reason);
+
+                // Without a fetch since boot, the failure started at boot.
+                let failing_for = last_fetch.unwrap_or(Instant::MIN).elapsed();
+                if let Some(url) = CONFIG.diagnostics_url
+                    && failing_for.as_secs() >= CONFIG.diagnostics_after_hours * 3600
+                {
+                    let bundle = diagnostics::bundle(failing_for.as_secs() / 3600, &recent_errors);
+                    match fetcher.post_json(url, bundle.as_bytes()).await {
+                        Ok(status) => println!("Diagnostics uploaded, status {}", status),
+                        Err(e) => println!("Failed to upload diagnostics: {:?}", e),
+                    }
+                }
+
+                if let Some(today) = today {
+                    events.retain(|event| event.dtstart >= Some(today));
+                }
+                info!("Using {} cached events", events.len());
+            }
+        }
+
+        let days_since_fetch =
+            last_fetch.map(|fetched_at| fetched_at.elapsed().as_secs() as i64 / SECONDS_PER_DAY);
+        let stale = days_since_fetch.is_none_or(|days| days >= stale_after_days);
+        match days_since_fetch {
+            Some(days) if stale => println!(
+                "Calendar data is stale, last successful fetch {} days ago",
+                days
+            ),
+            None => println!("No calendar data, no successful fetch since boot"),
+            _ => {}
+        }
+        status_led::set_stale(stale);
+
+        let Some(today) = today else {
+            status_led::set_state(SystemState::ClockNotSet);
+            for event in &events {
+                info!(
+                    "{} at {}-{}-{}",
+                    event.event_type,
+                    event.dtstart.unwrap().day() as u16,
+                    event.dtstart.unwrap().month() as u16,
+                    event.dtstart.unwrap().year() as u16,
+                );
+            }
+            pass = wait_for_next_cycle(
+                &mut socket,
+                &dns,
+                &mut clock,
+                &mut pass_times,
+                refresh_schedule,
+            )
+            .await;
+            continue;
+        };
+
+        if let Some(wall_clock) = clock {
+            upcoming::update(wall_clock, &events);
+        }
+
+        // All bins collected tomorrow go into a single reminder.
+        let tomorrow = today.next_day();
+        for event in &events {
+            info!(
+                "checking {} at {}-{}-{} ",
+                event.event_type,
+                event.dtstart.unwrap().day() as u16,
+                event.dtstart.unwrap().month() as u16,
+                event.dtstart.unwrap().year() as u16,
+            );
+        }
+        let bins =
+            tomorrow.map_or_else(Vec::new, |tomorrow| unacknowledged_bins(&events, tomorrow));
+
+        if pass == Pass::Reminder
+            && let Some(tomorrow) = tomorrow
+            && !bins.is_empty()
+        {
+            let mut text = reminder::render(reminder_template, language, &bins);
+            if let Some(region) = holiday_region
+                && let Some(holiday) = holidays::holiday_name(region, tomorrow)
+            {
+                println!(
+                    "Collection tomorrow falls on {}, it may be shifted",
+                    holiday
+                );
+                text.push(' ');
+                text.push_str(language.verify_note());
+            }
+            if stale {
+                text.push(' ');
+                text.push_str(language.stale_note());
+            }
+            if text.is_truncated() {
+                println!(
+                    "Reminder text cut to {} bytes, shorten reminder_template",
+                    reminder::REMINDER_TEXT_LEN
+                );
+            }
+            info!("{}", text.as_str());
+            let colors: Vec<_> = bins.iter().map(|bin| bin.style().color).collect();
+            status_led::set_reminder(&colors);
+            ack::shown(event_ids(tomorrow, &bins));
+            let mut names = Text::<MAX_AUDIT_NAMES_LEN>::new(Overflow::Cut);
+            for (i, bin) in bins.iter().enumerate() {
+                if i > 0 {
+                    names.push(',');
+                }
+                names.push_str(bin.style().short_label);
+            }
+            audit(
+                unix_time,
+                &AuditEvent::ReminderFired {
+                    event: names.as_str(),
+                    stale,
+                },
+            );
         }
+
+        pass = wait_for_next_cycle(
+            &mut socket,
+            &dns,
+            &mut clock,
+            &mut pass_times,
+            refresh_schedule,
+        )
+        .await;
     }
+}
 
-    loop {}
+/// The bins collected on `date` whose reminder was not acknowledged yet.
+fn unacknowledged_bins(events: &[IcsEvent], date: Date) -> Vec<Event> {
+    let mut bins = ics::bins_on(events, date);
+    bins.retain(|&event| !ack::is_acknowledged(EventId { date, event }));
+    bins
+}
+
+fn event_ids(date: Date, bins: &[Event]) -> Vec<EventId> {
+    bins.iter().map(|&event| EventId { date, event }).collect()
+}
+
+/// The passes of a day: the reminder pass fetches the calendar and reminds
+/// of tomorrow's collections, the optional collection day pass switches to
+/// today's and the optional refresh passes only fetch.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Pass {
+    Reminder,
+    CollectionDay,
+    Refresh,
+}
+
+/// The Unix times the last pass of each kind was scheduled for.
+#[derive(Default)]
+struct PassTimes([Option<i64>; 3]);
+
+impl PassTimes {
+    /// The time after which the next pass of kind `pass` is due: after every
+    /// pass so far, and [`MIN_PASS_GAP`] after the last one of its own kind.
+    /// Passes of other kinds never push it out.
+    fn after(&self, pass: Pass, now: i64) -> i64 {
+        let latest = self.0.iter().flatten().copied().fold(now, i64::max);
+        match self.0[pass as usize] {
+            Some(last) => latest.max(last + MIN_PASS_GAP),
+            None => latest,
+        }
+    }
+
+    fn record(&mut self, pass: Pass, time: i64) {
+        self.0[pass as usize] = Some(time);
+    }
+}
+
+/// Shows the outcome of a fetch, except that a refresh pass leaves a shown
+/// reminder alone until it is acknowledged.
+fn set_fetch_state(pass: Pass, state: SystemState) {
+    match pass {
+        Pass::Refresh => status_led::set_state_behind_due(state),
+        Pass::Reminder | Pass::CollectionDay => status_led::set_state(state),
+    }
+}
+
+/// Waits until the next pass at one of the configured local times and
+/// resyncs the clock. The wake instant comes from the drift corrected clock,
+/// so the pass keeps its time of day over weeks of uptime and across DST
+/// changes. While the clock is not set, SNTP is retried more often and the
+/// wait ends as soon as it succeeds, so reminders resume without waiting a
+/// full refresh interval.
+async fn wait_for_next_cycle(
+    socket: &mut UdpSocket<'_>,
+    dns: &Resolver<'_>,
+    clock: &mut Option<WallClock>,
+    pass_times: &mut PassTimes,
+    refresh_schedule: Option<Schedule>,
+) -> Pass {
+    let (next_refresh, pass) = match *clock {
+        Some(wall_clock) => {
+            let now = wall_clock.now();
+            let reminder = clock::next_local_time(
+                pass_times.after(Pass::Reminder, now),
+                CONFIG.reminder_hour,
+                CONFIG.reminder_minute,
+            );
+            let collection_day = CONFIG.collection_day_time.map(|(hour, minute)| {
+                clock::next_local_time(pass_times.after(Pass::CollectionDay, now), hour, minute)
+            });
+            let refresh = refresh_schedule
+                .and_then(|schedule| schedule.next_after(pass_times.after(Pass::Refresh, now)));
+            let mut next = (reminder, Pass::Reminder);
+            for (time, pass) in [
+                (collection_day, Pass::CollectionDay),
+                (refresh, Pass::Refresh),
+            ] {
+                if let Some(time) = time
+                    && time < next.0
+                {
+                    next = (time, pass);
+                }
+            }
+            pass_times.record(next.1, next.0);
+            (wall_clock.instant_at(next.0), next.1)
+        }
+        None => (Instant::now() + REFRESH_INTERVAL, Pass::Reminder),
+    };
+    loop {
+        let synced = clock.is_some();
+        let wake = if synced {
+            next_refresh
+        } else {
+            next_refresh.min(Instant::now() + CLOCK_RETRY_INTERVAL)
+        };
+        Timer::at(wake).await;
+        if synced && DEBUG_CLOCK {
+            return pass;
+        }
+
+        match ntp_request(socket, dns).await {
+            Ok(unix_time) => match clock {
+                Some(clock) => {
+                    clock.resync(unix_time);
+                    info!("Clock resynced, drift {} ppm", clock.drift_ppm());
+                }
+                None => {
+                    *clock =
+                        Some(WallClock::new(unix_time).with_speedup(CONFIG.debug_clock_speedup));
+                    info!("Clock set");
+                    return Pass::Reminder;
+                }
+            },
+            Err(()) => println!("SNTP sync failed"),
+        }
+        if Instant::now() >= next_refresh {
+            return pass;
+        }
+    }
 }
 
 async fn wait_for_connection(stack: Stack<'_>) {
@@ -289,26 +793,50 @@ async fn wait_for_connection(stack: Stack<'_>) {
     }
 }
 
+fn client_config(bssid: Option<[u8; 6]>, channel: Option<u8>) -> ModeConfig {
+    let mut config = ClientConfig::default()
+        .with_ssid(CONFIG.ssid.into())
+        .with_password(CONFIG.password.into());
+    if let Some(bssid) = bssid {
+        config = config.with_bssid(bssid);
+    }
+    if let Some(channel) = channel {
+        config = config.with_channel(channel);
+    }
+    ModeConfig::Client(config)
+}
+
 #[embassy_executor::task]
 async fn connection(mut controller: WifiController<'static>) {
     println!("start connection task");
-    println!("Device capabilities: {:?}", controller.capabilities());
     loop {
         match esp_radio::wifi::sta_state() {
             WifiStaState::Connected => {
                 // wait until we're no longer connected
-                controller.wait_for_event(WifiEvent::StaDisconnected).await;
+                let disconnected = controller.wait_for_event(WifiEvent::StaDisconnected);
+                match select(disconnected, RADIO_RESTART.wait()).await {
+                    Either::First(()) => netstats::increment(Counter::WifiDisconnect),
+                    Either::Second(()) => {
+                        println!("Restarting the radio");
+                        netstats::increment(Counter::RadioRestart);
+                        if let Err(e) = controller.stop_async().await {
+                            println!("Failed to stop wifi: {:?}", e);
+                        }
+                    }
+                }
                 Timer::after(Duration::from_millis(5000)).await
             }
             _ => {}
         }
+        let radio_on = profile::begin(Phase::RadioOn);
         if !matches!(controller.is_started(), Ok(true)) {
-            let client_config = ModeConfig::Client(
-                ClientConfig::default()
-                    .with_ssid(SSID.into())
-                    .with_password(PASSWORD.into()),
-            );
-            controller.set_config(&client_config).unwrap();
+            let bssid = match CONFIG.wifi_bssid {
+                BssidPin::Fixed(bssid) => Some(bssid),
+                BssidPin::Any | BssidPin::Strongest => None,
+            };
+            controller
+                .set_config(&client_config(bssid, CONFIG.wifi_channel))
+                .unwrap();
             println!("Starting wifi");
             controller.start_async().await.unwrap();
             println!("Wifi started!");
@@ -319,58 +847,271 @@ async fn connection(mut controller: WifiController<'static>) {
                 .scan_with_config_async(scan_config)
                 .await
                 .unwrap();
-            for ap in result {
-                println!("{:?}", ap);
+            scan::record(
+                result
+                    .into_iter()
+                    .map(|ap| AccessPoint {
+                        ssid: ap.ssid,
+                        bssid: ap.bssid,
+                        channel: ap.channel,
+                        rssi: ap.signal_strength,
+                    })
+                    .collect(),
+            );
+            match scan::strongest(CONFIG.ssid) {
+                Some(ap) => println!(
+                    "Best signal for {}: channel {}, {} dBm",
+                    CONFIG.ssid, ap.channel, ap.rssi
+                ),
+                None => println!("{} not found in the scan", CONFIG.ssid),
+            }
+            if matches!(CONFIG.wifi_bssid, BssidPin::Strongest)
+                && let Some(ap) = scan::strongest(CONFIG.ssid)
+            {
+                println!("Pinning to AP {:02x?}", ap.bssid);
+                controller
+                    .set_config(&client_config(Some(ap.bssid), Some(ap.channel)))
+                    .unwrap();
             }
         }
         println!("About to connect...");
 
-        match controller.connect_async().await {
-            Ok(_) => println!("Wifi connected!"),
+        let connected = controller.connect_async().await;
+        drop(radio_on);
+        match connected {
+            Ok(_) => {
+                println!("Wifi connected!");
+                // A restart asked for while disconnected is moot now.
+                RADIO_RESTART.reset();
+            }
             Err(e) => {
                 println!("Failed to connect to wifi: {:?}", e);
+                netstats::increment(Counter::WifiConnectFailure);
                 Timer::after(Duration::from_millis(5000)).await
             }
         }
     }
 }
 
+/// Acknowledges a shown reminder when the button is pressed, or else
+/// announces the next collection.
+#[embassy_executor::task]
+async fn button_task(mut button: Input<'static>) {
+    loop {
+        button.wait_for_falling_edge().await;
+        if ack::acknowledge("button") {
+            info!("Reminder acknowledged");
+        } else {
+            upcoming::announce();
+        }
+        Timer::after(BUTTON_DEBOUNCE).await;
+        button.wait_for_high().await;
+    }
+}
+
+#[embassy_executor::task]
+async fn flag_servo(ledc: LEDC<'static>, pin: GPIO13<'static>, servo: FlagServo) {
+    flag::run(ledc, pin, servo).await
+}
+
+/// Acknowledges a shown reminder when the bin shed door opens, i.e. when the
+/// reed switch stays open for the debounce time.
+#[embassy_executor::task]
+async fn door_sensor(mut door: Input<'static>) {
+    loop {
+        door.wait_for_rising_edge().await;
+        Timer::after(DOOR_DEBOUNCE).await;
+        if door.is_high() && ack::acknowledge("door") {
+            info!("Shed door opened, reminder acknowledged");
+        }
+        door.wait_for_low().await;
+        Timer::after(DOOR_DEBOUNCE).await;
+    }
+}
+
+/// Switches the night light on and off at the scheduled local times. Waits
+/// for the clock to be set first.
+#[embassy_executor::task]
+async fn night_light(schedule: NightLight) {
+    // The last switching time, so a wake a fraction of a second early doesn't
+    // find the same switching time again.
+    let mut switched_at = 0;
+    loop {
+        let Some(wall_clock) = clock::shared() else {
+            Timer::after(CLOCK_RETRY_INTERVAL).await;
+            continue;
+        };
+        let now = wall_clock.now().max(switched_at);
+        let start = clock::next_local_time(now, schedule.start_hour, schedule.start_minute);
+        let end = clock::next_local_time(now, schedule.end_hour, schedule.end_minute);
+        // Inside the window when it ends before it starts again.
+        let on = end < start;
+        status_led::set_night_light(if on { schedule.brightness } else { 0 });
+        switched_at = start.min(end);
+        Timer::at(wall_clock.instant_at(switched_at)).await;
+    }
+}
+
+/// Counts link and DHCP transitions and logs the network counters
+/// periodically. The transition times also feed the entropy pool.
+#[embassy_executor::task]
+async fn net_monitor(stack: Stack<'static>) {
+    let mut link_up = false;
+    let mut configured = false;
+    let mut last_report = Instant::now();
+    loop {
+        if stack.is_link_up() != link_up {
+            link_up = !link_up;
+            netstats::increment(if link_up {
+                Counter::LinkUp
+            } else {
+                Counter::LinkDown
+            });
+            entropy::mix_timing();
+        }
+        if stack.config_v4().is_some() != configured {
+            configured = !configured;
+            netstats::increment(if configured {
+                Counter::DhcpLease
+            } else {
+                Counter::DhcpLost
+            });
+            entropy::mix_timing();
+        }
+        if last_report.elapsed() >= NET_STATS_INTERVAL {
+            info!("Network stats: {}", netstats::snapshot());
+            last_report = Instant::now();
+        }
+        Timer::after(Duration::from_secs(1)).await;
+    }
+}
+
 #[embassy_executor::task]
 async fn net_task(mut runner: Runner<'static, WifiDevice<'static>>) {
     runner.run().await
 }
 
-async fn get_ics(stack: Stack<'_>, tls_seed: u64) -> String {
-    let mut rx_buffer = [0; RX_BUFFER_SIZE];
-    let mut tx_buffer = [0; 4096];
-    let dns = DnsSocket::new(stack);
-    let tcp_state = TcpClientState::<1, 4096, RX_BUFFER_SIZE>::new();
-    let tcp = TcpClient::new(stack, &tcp_state);
+/// Recovers from a stack that has a link but passes no traffic: restarts the
+/// radio after `watchdog_failures` fetches in a row failed while offline, and
+/// reboots after twice as many if the gateway doesn't answer either.
+///
+/// A reboot loses the cached dates and the clock, so it is kept for a broken
+/// local connection. An outage upstream of the gateway, or a LAN source that
+/// is down, is waited out on the cached dates instead.
+async fn connectivity_watchdog(stack: Stack<'_>, offline_failures: u32) {
+    let limit = CONFIG.watchdog_failures;
+    if limit == 0 {
+        return;
+    }
+    if offline_failures >= 2 * limit {
+        let local_broken = match stack.config_v4() {
+            None => true,
+            Some(config) => match config.gateway {
+                Some(gateway) => ping::ping(stack, gateway).await.is_err(),
+                None => false,
+            },
+        };
+        if local_broken {
+            println!(
+                "Still offline after {} fetches and the gateway doesn't answer, rebooting",
+                offline_failures
+            );
+            software_reset();
+        }
+        println!(
+            "Offline for {} fetches, but the gateway answers, not rebooting",
+            offline_failures
+        );
+    }
+    if offline_failures == limit {
+        println!(
+            "Offline for {} fetches, restarting the radio",
+            offline_failures
+        );
+        RADIO_RESTART.signal(());
+    }
+}
 
-    let tls = TlsConfig::new(
-        tls_seed,
-        &mut rx_buffer,
-        &mut tx_buffer,
-        reqwless::client::TlsVerify::None,
-    );
+#[derive(Debug, PartialEq, Eq)]
+enum Connectivity {
+    Online,
+    CaptivePortal,
+    Offline,
+}
 
-    let mut client = HttpClient::new_with_tls(&tcp, &dns, tls);
-    let mut buffer = [0u8; RX_BUFFER_SIZE];
-    let mut http_req = client
-        .request(
-            reqwless::request::Method::GET,
-            "https://backend.stadtreinigung.hamburg/kalender/abholtermine.ics?hnIds=44353",
-        )
+/// Requests a URL that answers with an empty 204 on the open internet. Any
+/// other answer means something on the network intercepts plain HTTP, which
+/// is what captive portals do before the user signed in.
+async fn probe_connectivity(stack: Stack<'_>, dns_servers: &[Ipv4Address]) -> Connectivity {
+    let dns = Resolver::new(stack, dns_servers);
+    let tcp_state = TcpClientState::<1, 1024, 1024>::new();
+    let tcp = TcpClient::new(stack, &tcp_state);
+    let mut client = HttpClient::new(&tcp, &dns);
+    let mut buffer = [0u8; 1024];
+
+    let Ok(mut http_req) = client
+        .request(reqwless::request::Method::GET, CONNECTIVITY_CHECK_URL)
         .await
-        .unwrap();
-    info!("requesting");
-    let response = http_req.send(&mut buffer).await.unwrap();
+    else {
+        return Connectivity::Offline;
+    };
+    match http_req.send(&mut buffer).await {
+        Ok(response) if response.status.0 == 204 => Connectivity::Online,
+        Ok(response) => {
+            println!(
+                "Connectivity check answered with status {}",
+                response.status.0
+            );
+            Connectivity::CaptivePortal
+        }
+        Err(_) => Connectivity::Offline,
+    }
+}
+
+/// Whether the calendar is served from the local network. Its fetches then
+/// don't depend on the internet, and neither should the diagnosis of a
+/// failed one.
+fn calendar_on_lan() -> bool {
+    let host = fetch::url_host(CONFIG.calendar_url);
+    match host.parse::<Ipv4Address>() {
+        Ok(address) => address.is_private() || address.is_link_local(),
+        Err(_) => host.trim_end_matches('.').ends_with(".local"),
+    }
+}
 
-    info!("Got response");
-    let res = response.body().read_to_end().await.unwrap();
+/// [`probe_connectivity`] for a calendar on the local network: the network
+/// is up if the gateway answers a ping.
+async fn probe_gateway(stack: Stack<'_>) -> Connectivity {
+    let Some(gateway) = stack.config_v4().and_then(|config| config.gateway) else {
+        return Connectivity::Offline;
+    };
+    match ping::ping(stack, gateway).await {
+        Ok(_) => Connectivity::Online,
+        Err(_) => Connectivity::Offline,
+    }
+}
 
-    let content = core::str::from_utf8(res).unwrap();
-    let mut s = String::new();
-    s.push_str(content);
-    return s;
+/// Pings the gateway and, unless the calendar is on the local network, a
+/// host on the internet, to tell where a failed fetch got stuck.
+async fn ping_report(stack: Stack<'_>, dns_servers: &[Ipv4Address]) -> String {
+    let Some(config) = stack.config_v4() else {
+        return String::from("no IP address");
+    };
+    let internet =
+        (!calendar_on_lan()).then(|| dns_servers.first().copied().unwrap_or(PING_INTERNET_HOST));
+    let mut report = String::new();
+    for (name, address) in [("gateway", config.gateway), ("internet", internet)] {
+        let Some(address) = address else {
+            continue;
+        };
+        let result = match ping::ping(stack, address).await {
+            Ok(round_trip) => format!("{} ms", round_trip.as_millis()),
+            Err(e) => format!("{:?}", e),
+        };
+        if !report.is_empty() {
+            report.push_str(", ");
+        }
+        report.push_str(&format!("ping {} {}: {}", name, address, result));
+    }
+    report
 }
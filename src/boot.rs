@@ -0,0 +1,110 @@
+//! One summary of what a unit runs and how it is set up, logged once at boot
+//! instead of piecemeal while the hardware comes up. It is kept for the
+//! diagnostics bundle, so an upload tells the whole setup.
+//!
+//! The summary is a single line of `key=value` pairs: firmware, identity,
+//! calendar host, WiFi, the enabled outputs and the configured times.
+
+use alloc::string::String;
+use core::cell::RefCell;
+use core::fmt::Write as _;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+use crate::config::{BssidPin, CONFIG, NtpServer};
+use crate::fetch::url_host;
+use crate::{device, version};
+
+static SUMMARY: Mutex<CriticalSectionRawMutex, RefCell<String>> =
+    Mutex::new(RefCell::new(String::new()));
+
+fn build(capabilities: &str) -> String {
+    let mut summary = String::new();
+    let _ = write!(
+        summary,
+        "firmware={} git={} built={} features=[{}] device={}",
+        version::VERSION,
+        version::GIT_HASH,
+        version::BUILD_TIME,
+        version::ENABLED_FEATURES,
+        device::id()
+    );
+    let _ = write!(
+        summary,
+        " calendar={} ssid={}",
+        url_host(CONFIG.calendar_url),
+        CONFIG.ssid
+    );
+    match CONFIG.ntp_server {
+        NtpServer::Address(address) => {
+            let _ = write!(summary, " ntp={}", address);
+        }
+        NtpServer::Host(host) => {
+            let _ = write!(summary, " ntp={}", host);
+        }
+    }
+    match CONFIG.wifi_bssid {
+        BssidPin::Any => {}
+        BssidPin::Strongest => summary.push_str(" bssid=strongest"),
+        BssidPin::Fixed(bssid) => {
+            let _ = write!(summary, " bssid={:02x?}", bssid);
+        }
+    }
+    if let Some(channel) = CONFIG.wifi_channel {
+        let _ = write!(summary, " channel={}", channel);
+    }
+    let _ = write!(summary, " radio={}", capabilities);
+
+    summary.push_str(" outputs=led");
+    if CONFIG.flag_servo.is_some() {
+        summary.push_str(",flag");
+    }
+    if let Some(host) = CONFIG.syslog_host {
+        let _ = write!(summary, ",syslog@{}", host);
+    }
+    if let Some(url) = CONFIG.diagnostics_url {
+        let _ = write!(summary, ",diagnostics@{}", url_host(url));
+    }
+    if CONFIG.door_sensor {
+        summary.push_str(" inputs=button,door");
+    } else {
+        summary.push_str(" inputs=button");
+    }
+
+    let _ = write!(
+        summary,
+        " reminder={:02}:{:02}",
+        CONFIG.reminder_hour, CONFIG.reminder_minute
+    );
+    if let Some((hour, minute)) = CONFIG.collection_day_time {
+        let _ = write!(summary, " collection_day={:02}:{:02}", hour, minute);
+    }
+    if let Some(schedule) = CONFIG.refresh_schedule {
+        let _ = write!(summary, " refresh=\"{}\"", schedule.spec());
+    }
+    if let Some(light) = CONFIG.night_light {
+        let _ = write!(
+            summary,
+            " night_light={:02}:{:02}-{:02}:{:02}",
+            light.start_hour, light.start_minute, light.end_hour, light.end_minute
+        );
+    }
+    if let Some(region) = CONFIG.holiday_region {
+        let _ = write!(summary, " holidays={:?}", region);
+    }
+    summary
+}
+
+/// Builds the summary, logs it and keeps it for [`summary`]. `capabilities`
+/// describes the radio.
+pub fn log(capabilities: &str) {
+    let built = build(capabilities);
+    defmt::info!("Boot: {}", built.as_str());
+    SUMMARY.lock(|summary| *summary.borrow_mut() = built);
+}
+
+/// The summary logged at boot, empty before.
+pub fn summary() -> String {
+    SUMMARY.lock(|summary| summary.borrow().clone())
+}
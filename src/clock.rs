@@ -0,0 +1,247 @@
+//! Wall-clock time between SNTP syncs.
+//!
+//! The monotonic timer runs off the crystal and drifts against real time by
+//! some ppm. Each resync measures that drift over the interval since the
+//! previous sync and the clock corrects for it until the next one.
+//!
+//! Dates and times of day are local to Germany: CET, with EU summer time.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::{Duration, Instant};
+use time::{Date, Month, UtcDateTime, Weekday};
+
+const MICROS_PER_SEC: i64 = 1_000_000;
+/// Shorter intervals are dominated by the one second SNTP resolution.
+const MIN_DRIFT_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+/// Anything beyond this is a clock step on the server, not crystal drift.
+const MAX_DRIFT_PPM: i64 = 500;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+const UNIX_EPOCH_JULIAN_DAY: i64 = 2_440_588;
+const CET_OFFSET: i64 = 3600;
+const CEST_OFFSET: i64 = 7200;
+
+static SHARED: Mutex<CriticalSectionRawMutex, Cell<Option<WallClock>>> =
+    Mutex::new(Cell::new(None));
+
+/// Unix time derived from the last SNTP sync and the monotonic timer.
+#[derive(Debug, Clone, Copy)]
+pub struct WallClock {
+    unix_at_sync: i64,
+    synced_at: Instant,
+    /// How much faster real time runs than the monotonic timer, in ppm.
+    drift_ppm: i64,
+    /// Debug fast-forward factor, 1 in normal operation.
+    speedup: i64,
+}
+
+impl WallClock {
+    pub fn new(unix_time: i64) -> Self {
+        WallClock {
+            unix_at_sync: unix_time,
+            synced_at: Instant::now(),
+            drift_ppm: 0,
+            speedup: 1,
+        }
+    }
+
+    /// Runs the clock `factor` times faster than real time, for exercising
+    /// the scheduler on hardware. Resyncing such a clock makes no sense.
+    pub fn with_speedup(self, factor: i64) -> Self {
+        WallClock {
+            speedup: factor.max(1),
+            ..self
+        }
+    }
+
+    /// Restarts the clock at `unix_time` and updates the drift estimate from
+    /// the interval since the previous sync.
+    pub fn resync(&mut self, unix_time: i64) {
+        let elapsed = self.synced_at.elapsed();
+        if elapsed >= MIN_DRIFT_INTERVAL {
+            let elapsed_us = elapsed.as_micros() as i64;
+            let wall_us = (unix_time - self.unix_at_sync) * MICROS_PER_SEC;
+            let drift_ppm = (wall_us - elapsed_us) * MICROS_PER_SEC / elapsed_us;
+            if drift_ppm.abs() <= MAX_DRIFT_PPM {
+                self.drift_ppm = drift_ppm;
+            } else {
+                defmt::warn!("Ignoring implausible clock drift of {} ppm", drift_ppm);
+            }
+        }
+        self.unix_at_sync = unix_time;
+        self.synced_at = Instant::now();
+    }
+
+    pub fn drift_ppm(&self) -> i64 {
+        self.drift_ppm
+    }
+
+    pub fn now(&self) -> i64 {
+        let elapsed_us = self.synced_at.elapsed().as_micros() as i64;
+        let wall_us = (elapsed_us + elapsed_us * self.drift_ppm / MICROS_PER_SEC) * self.speedup;
+        self.unix_at_sync + wall_us / MICROS_PER_SEC
+    }
+
+    /// The local date.
+    pub fn today(&self) -> Date {
+        local_date(self.now())
+    }
+
+    /// The monotonic instant at which the clock will read `unix_time`.
+    pub fn instant_at(&self, unix_time: i64) -> Instant {
+        let wall_us = (unix_time - self.unix_at_sync) * MICROS_PER_SEC / self.speedup;
+        let elapsed_us = wall_us * MICROS_PER_SEC / (MICROS_PER_SEC + self.drift_ppm);
+        self.synced_at + Duration::from_micros(elapsed_us.max(0) as u64)
+    }
+}
+
+/// Makes `clock` available to other tasks through [`shared`].
+pub fn publish(clock: WallClock) {
+    SHARED.lock(|shared| shared.set(Some(clock)));
+}
+
+/// The most recently published clock, `None` until one is set.
+pub fn shared() -> Option<WallClock> {
+    SHARED.lock(|shared| shared.get())
+}
+
+/// Last Sunday of `month`, which must have 31 days.
+fn last_sunday(year: i32, month: Month) -> Option<Date> {
+    let mut date = Date::from_calendar_date(year, month, 31).ok()?;
+    while date.weekday() != Weekday::Sunday {
+        date = date.previous_day()?;
+    }
+    Some(date)
+}
+
+/// Offset of local time from UTC at `unix_time`. Summer time runs from 01:00
+/// UTC on the last Sunday in March to 01:00 UTC on the last Sunday in
+/// October.
+pub fn utc_offset(unix_time: i64) -> i64 {
+    let Ok(utc) = UtcDateTime::from_unix_timestamp(unix_time) else {
+        return CET_OFFSET;
+    };
+    let transition = |month| {
+        last_sunday(utc.year(), month)
+            .map(|date| date.midnight().assume_utc().unix_timestamp() + 3600)
+    };
+    match (transition(Month::March), transition(Month::October)) {
+        (Some(start), Some(end)) if (start..end).contains(&unix_time) => CEST_OFFSET,
+        _ => CET_OFFSET,
+    }
+}
+
+pub fn local_date(unix_time: i64) -> Date {
+    checked_local_date(unix_time).expect("SNTP time is within the supported range")
+}
+
+/// Like [`local_date`], but `None` outside the range of dates `time` supports,
+/// for times that don't come from SNTP.
+pub fn checked_local_date(unix_time: i64) -> Option<Date> {
+    let local = unix_time.checked_add(utc_offset(unix_time))?;
+    UtcDateTime::from_unix_timestamp(local)
+        .ok()
+        .map(|local| local.date())
+}
+
+/// The first Unix time after `after` at which the local time reads
+/// `hour:minute`.
+///
+/// A time inside the hour skipped when summer time starts is moved forward
+/// by that hour. A time inside the hour repeated when it ends is taken at its
+/// first occurrence.
+pub fn next_local_time(after: i64, hour: u8, minute: u8) -> i64 {
+    let mut day = (after + utc_offset(after)).div_euclid(SECONDS_PER_DAY);
+    loop {
+        let candidate = local_time_on_day(day, hour, minute);
+        if candidate > after {
+            return candidate;
+        }
+        day += 1;
+    }
+}
+
+/// The Unix time at which the local time on `date` reads `hour:minute`,
+/// resolved around DST changes like [`next_local_time`].
+pub fn local_time_on(date: Date, hour: u8, minute: u8) -> i64 {
+    let day = date.to_julian_day() as i64 - UNIX_EPOCH_JULIAN_DAY;
+    local_time_on_day(day, hour, minute)
+}
+
+/// `day` counts days since the Unix epoch.
+fn local_time_on_day(day: i64, hour: u8, minute: u8) -> i64 {
+    let local = day * SECONDS_PER_DAY + hour as i64 * 3600 + minute as i64 * 60;
+    let summer = local - CEST_OFFSET;
+    // If the summer time reading doesn't exist, the winter time one is
+    // either the valid reading or, inside the skipped hour, the instant
+    // one hour later.
+    if utc_offset(summer) == CEST_OFFSET {
+        summer
+    } else {
+        local - CET_OFFSET
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Starts and ends of summer time from the tz database's Europe/Berlin.
+    const TRANSITIONS: &[(i64, i64)] = &[
+        (828_234_000, 846_378_000),
+        (954_032_400, 972_781_200),
+        (1_711_846_800, 1_729_990_800),
+        (1_743_296_400, 1_761_440_400),
+        (2_153_350_800, 2_172_099_600),
+        (4_109_878_800, 4_128_627_600),
+    ];
+
+    #[test]
+    fn utc_offset_switches_at_both_transitions() {
+        for &(start, end) in TRANSITIONS {
+            assert_eq!(utc_offset(start - 1), CET_OFFSET);
+            assert_eq!(utc_offset(start), CEST_OFFSET);
+            assert_eq!(utc_offset(end - 1), CEST_OFFSET);
+            assert_eq!(utc_offset(end), CET_OFFSET);
+        }
+    }
+
+    #[test]
+    fn next_local_time_moves_skipped_time_forward() {
+        // 2025-03-30 00:00 CET.
+        let midnight = 1_743_289_200;
+        // 02:30 doesn't exist that day; 03:30 CEST is 01:30 UTC.
+        assert_eq!(next_local_time(midnight, 2, 30), 1_743_298_200);
+    }
+
+    #[test]
+    fn next_local_time_takes_first_of_repeated_time() {
+        // 2025-10-26 00:00 CEST.
+        let midnight = 1_761_429_600;
+        // 02:30 happens twice that day; the first, in CEST, is 00:30 UTC.
+        assert_eq!(next_local_time(midnight, 2, 30), 1_761_438_600);
+    }
+
+    #[test]
+    fn next_local_time_is_strictly_after() {
+        // 2025-01-10 18:00 CET.
+        let reminder = 1_736_528_400;
+        assert_eq!(next_local_time(reminder - 1, 18, 0), reminder);
+        assert_eq!(next_local_time(reminder, 18, 0), reminder + SECONDS_PER_DAY);
+    }
+
+    #[test]
+    fn local_date_changes_at_local_midnight() {
+        let date = |year, month, day| Date::from_calendar_date(year, month, day).unwrap();
+        // Winter: midnight is 23:00 UTC.
+        assert_eq!(local_date(1_736_549_999), date(2025, Month::January, 10));
+        assert_eq!(local_date(1_736_550_000), date(2025, Month::January, 11));
+        assert_eq!(local_date(1_736_555_400), date(2025, Month::January, 11));
+        // Summer: midnight is 22:00 UTC.
+        assert_eq!(local_date(1_752_184_799), date(2025, Month::July, 10));
+        assert_eq!(local_date(1_752_184_800), date(2025, Month::July, 11));
+    }
+}
@@ -0,0 +1,118 @@
+//! Build-time configuration.
+//!
+//! `build.rs` generates [`CONFIG`] from `config.toml` (see
+//! `config.example.toml`). Each key can be overridden by the environment
+//! variable of the same name in upper case, e.g. `SSID=... cargo run`.
+//! Values are parsed and checked there, so a bad one fails the build.
+
+use embassy_net::Ipv4Address;
+
+use crate::cron;
+use crate::holidays::Region;
+use crate::reminder::Language;
+use crate::schedule::Schedule;
+
+pub struct Config {
+    pub ssid: &'static str,
+    pub password: &'static str,
+    /// Access point to connect to when several serve the network.
+    pub wifi_bssid: BssidPin,
+    /// 2.4 GHz channel of the access point, skips scanning other channels.
+    pub wifi_channel: Option<u8>,
+    /// URL of the ICS calendar.
+    pub calendar_url: &'static str,
+    /// SNTP server, `pool.ntp.org` unless configured.
+    pub ntp_server: NtpServer,
+    /// Optional RFC 5424 syslog collector for audit events.
+    pub syslog_host: Option<Ipv4Address>,
+    /// Endpoint that receives a JSON diagnostic bundle while fetches fail.
+    pub diagnostics_url: Option<&'static str>,
+    /// Hours without a successful fetch before diagnostics are uploaded.
+    pub diagnostics_after_hours: u64,
+    /// DNS servers used instead of the DHCP provided ones.
+    pub dns_servers: &'static [Ipv4Address],
+    /// Language of reminder texts, `de` (default) or `en`.
+    pub reminder_language: Language,
+    /// Reminder text with `{bins}` and `{count}` placeholders, overriding the
+    /// language's default template.
+    pub reminder_template: Option<&'static str>,
+    /// German state, configured by its two-letter code (e.g. `HH`), enabling
+    /// the public-holiday check of collection dates.
+    pub holiday_region: Option<Region>,
+    /// Local time of the daily pass that fetches the calendar and reminds of
+    /// the next day's collections.
+    pub reminder_hour: u8,
+    pub reminder_minute: u8,
+    /// Extra calendar refreshes, configured as a cron-like spec.
+    pub refresh_schedule: Option<Schedule>,
+    /// Local hour and minute of an optional morning pass that switches the
+    /// reminder to the collection day indicator.
+    pub collection_day_time: Option<(u8, u8)>,
+    /// How far ahead parsed events are kept, in weeks.
+    pub event_horizon_weeks: u16,
+    /// Upper bound on the number of stored events.
+    pub max_events: usize,
+    /// Days without a successful fetch after which cached events count as
+    /// stale.
+    pub stale_after_days: i64,
+    /// Downloads per month after which the calendar is only fetched every
+    /// other day, in kB. 0 for no limit.
+    pub monthly_traffic_budget_kb: u64,
+    /// Local hours during which the idle LED glows dimly.
+    pub night_light: Option<NightLight>,
+    /// Servo flag on GPIO13, raised while bins are due.
+    pub flag_servo: Option<FlagServo>,
+    /// Reed switch on GPIO14 at the bin shed door. Opening the door
+    /// acknowledges a shown reminder.
+    pub door_sensor: bool,
+    /// Failed fetches in a row without connectivity after which the radio is
+    /// restarted; after twice as many the device reboots, unless the gateway
+    /// still answers. 0 disables this.
+    pub watchdog_failures: u32,
+    /// Fire a made-up reminder at boot to check the outputs.
+    pub test_reminder: bool,
+    /// Unix time to start the clock at instead of the SNTP time.
+    pub debug_start_time: Option<i64>,
+    /// Factor by which the clock runs faster than real time.
+    pub debug_clock_speedup: i64,
+    /// Drive GPIO4 high during each profiled phase and log phase durations.
+    pub power_profile: bool,
+    pub heap_size: usize,
+    /// Size of the TLS receive buffer and of the response buffer.
+    pub rx_buffer_size: usize,
+}
+
+#[derive(Clone, Copy)]
+pub enum BssidPin {
+    /// Let the WiFi driver pick.
+    Any,
+    Fixed([u8; 6]),
+    /// The access point with the best signal in the boot scan.
+    Strongest,
+}
+
+#[derive(Clone, Copy)]
+pub enum NtpServer {
+    Address(Ipv4Address),
+    /// Resolved like the calendar host before each sync.
+    Host(&'static str),
+}
+
+#[derive(Clone, Copy)]
+pub struct NightLight {
+    pub start_hour: u8,
+    pub start_minute: u8,
+    pub end_hour: u8,
+    pub end_minute: u8,
+    /// LED brightness of the night light, 0 to 255.
+    pub brightness: u8,
+}
+
+#[derive(Clone, Copy)]
+pub struct FlagServo {
+    /// Servo angles in degrees, 0 to 180.
+    pub raised_angle: u8,
+    pub lowered_angle: u8,
+}
+
+include!(concat!(env!("OUT_DIR"), "/config.rs"));
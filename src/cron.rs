@@ -0,0 +1,74 @@
+//! Parsing of the cron-like specs of `refresh_schedule`.
+//!
+//! `build.rs` includes this module as well, so a bad spec fails the build
+//! instead of the boot. It must therefore not depend on anything but `core`.
+
+/// The times a spec selects, as bit sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CronFields {
+    /// Bit n set for minute n.
+    pub minutes: u64,
+    pub hours: u32,
+    /// Bit 0 for Sunday.
+    pub weekdays: u8,
+}
+
+/// Bits of the values `field` selects, from `min` to `max`.
+fn parse_field(field: &str, min: u8, max: u8) -> Result<u64, &'static str> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u8>()
+                    .ok()
+                    .filter(|&step| step > 0)
+                    .ok_or("invalid step")?,
+            ),
+            None => (part, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (
+                    first.parse().map_err(|_| "invalid number")?,
+                    last.parse().map_err(|_| "invalid number")?,
+                ),
+                None => {
+                    let value = range.parse().map_err(|_| "invalid number")?;
+                    (value, value)
+                }
+            },
+        };
+        if first < min || last > max || first > last {
+            return Err("value out of range");
+        }
+        for value in (first..=last).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+/// Parses `minute hour day month weekday`. Day of month and month must be
+/// `*`, and weekday 7 is Sunday as well.
+pub fn parse(spec: &str) -> Result<CronFields, &'static str> {
+    let mut fields = [""; 5];
+    let mut count = 0;
+    for field in spec.split_whitespace() {
+        *fields.get_mut(count).ok_or("expected five fields")? = field;
+        count += 1;
+    }
+    if count != fields.len() {
+        return Err("expected five fields");
+    }
+    if fields[2] != "*" || fields[3] != "*" {
+        return Err("day of month and month must be *");
+    }
+    let weekdays = parse_field(fields[4], 0, 7)?;
+    Ok(CronFields {
+        minutes: parse_field(fields[0], 0, 59)?,
+        hours: parse_field(fields[1], 0, 23)? as u32,
+        weekdays: (weekdays | weekdays >> 7) as u8 & 0x7F,
+    })
+}
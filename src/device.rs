@@ -0,0 +1,49 @@
+//! Identity of this unit, so several units on one network can be told apart.
+//!
+//! The ID is the lower half of the factory-programmed MAC address from eFuse,
+//! which is stable across reflashing and unique per chip.
+
+use core::fmt::{self, Write as _};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::text::{Overflow, Text};
+
+const HOSTNAME_PREFIX: &str = "muellabfuhr";
+/// The prefix, a dash and six hex digits.
+pub const HOSTNAME_LEN: usize = HOSTNAME_PREFIX.len() + 7;
+
+static ID: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DeviceId(u32);
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:06x}", self.0)
+    }
+}
+
+impl defmt::Format for DeviceId {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=u32:06x}", self.0)
+    }
+}
+
+/// Sets the device ID from the station MAC address.
+pub fn init(mac: [u8; 6]) {
+    ID.store(
+        u32::from_be_bytes([0, mac[3], mac[4], mac[5]]),
+        Ordering::Relaxed,
+    );
+}
+
+pub fn id() -> DeviceId {
+    DeviceId(ID.load(Ordering::Relaxed))
+}
+
+/// Host name announced over DHCP and used in syslog messages.
+pub fn hostname() -> Text<HOSTNAME_LEN> {
+    let mut hostname = Text::new(Overflow::Cut);
+    let _ = write!(hostname, "{}-{}", HOSTNAME_PREFIX, id());
+    hostname
+}
@@ -0,0 +1,64 @@
+//! Compact diagnostic bundle, uploaded when fetches keep failing so that
+//! whoever looks after a unit hears about it before collections are missed.
+
+use alloc::string::String;
+use core::fmt::Write as _;
+
+use embassy_time::Instant;
+
+use crate::config::CONFIG;
+use crate::{boot, device, netstats, scan, version};
+
+/// Appends `value` as a JSON string literal.
+fn push_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Formats the bundle as JSON: identity, firmware, uptime, heap, signal,
+/// network counters, hours since the last successful fetch, the most recent
+/// errors, oldest first, and the boot summary.
+pub fn bundle(hours_failing: u64, recent_errors: &[String]) -> String {
+    let mut json = String::new();
+    let _ = write!(json, "{{\"device\":\"{}\",\"version\":", device::id());
+    push_json_string(&mut json, version::VERSION);
+    json.push_str(",\"git\":");
+    push_json_string(&mut json, version::GIT_HASH);
+    let _ = write!(
+        json,
+        ",\"uptime_s\":{},\"heap_free\":{},\"heap_used\":{}",
+        Instant::now().as_secs(),
+        esp_alloc::HEAP.free(),
+        esp_alloc::HEAP.used()
+    );
+    if let Some(ap) = scan::strongest(CONFIG.ssid) {
+        let _ = write!(json, ",\"rssi\":{}", ap.rssi);
+    }
+    let stats = netstats::snapshot();
+    let _ = write!(
+        json,
+        ",\"wifi_disconnects\":{},\"radio_restarts\":{},\"socket_errors\":{}",
+        stats.wifi_disconnects, stats.radio_restarts, stats.socket_errors
+    );
+    let _ = write!(json, ",\"hours_failing\":{},\"errors\":[", hours_failing);
+    for (i, error) in recent_errors.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        push_json_string(&mut json, error);
+    }
+    json.push_str("],\"boot\":");
+    push_json_string(&mut json, &boot::summary());
+    json.push('}');
+    json
+}
@@ -0,0 +1,217 @@
+//! Hostname resolution with optional statically configured DNS servers.
+//!
+//! Without configured servers, queries go through embassy-net's DNS socket
+//! and therefore to the servers handed out by DHCP. With configured servers,
+//! a plain A query is sent to each of them in turn over UDP.
+//!
+//! Names under `.local` are resolved with a one-shot multicast DNS query
+//! (RFC 6762, section 5.1) instead, so LAN hosts such as a NAS can be
+//! reached by name without a DNS server that knows them.
+
+use core::net::IpAddr;
+
+use embassy_net::dns::DnsSocket;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpEndpoint, Ipv4Address, Stack};
+use embassy_time::{Duration, Instant, with_timeout};
+use embedded_nal_async::{AddrType, Dns};
+
+const DNS_PORT: u16 = 53;
+const MDNS_GROUP: Ipv4Address = Ipv4Address::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum size of a DNS message over UDP without EDNS.
+const MAX_MESSAGE_SIZE: usize = 512;
+const TYPE_A: u16 = 1;
+const CLASS_IN: u16 = 1;
+/// mDNS responders may set the top bit of the class ("cache flush").
+const CLASS_MASK: u16 = 0x7FFF;
+
+#[derive(Debug)]
+pub enum DnsError {
+    /// Only IPv4 lookups are supported.
+    Unsupported,
+    InvalidName,
+    Socket,
+    Timeout,
+    Malformed,
+    NotFound,
+    Dhcp(embassy_net::dns::Error),
+}
+
+pub struct Resolver<'a> {
+    stack: Stack<'a>,
+    dhcp: DnsSocket<'a>,
+    servers: &'a [Ipv4Address],
+}
+
+impl<'a> Resolver<'a> {
+    /// Resolves through `servers` if any are given, otherwise through the
+    /// DHCP provided servers.
+    pub fn new(stack: Stack<'a>, servers: &'a [Ipv4Address]) -> Self {
+        Resolver {
+            stack,
+            dhcp: DnsSocket::new(stack),
+            servers,
+        }
+    }
+
+    async fn query(&self, server: IpEndpoint, host: &str) -> Result<Ipv4Address, DnsError> {
+        let mut rx_meta = [PacketMetadata::EMPTY; 1];
+        let mut tx_meta = [PacketMetadata::EMPTY; 1];
+        let mut rx_buf = [0u8; MAX_MESSAGE_SIZE];
+        let mut tx_buf = [0u8; MAX_MESSAGE_SIZE];
+        let mut socket = UdpSocket::new(
+            self.stack,
+            &mut rx_meta,
+            &mut rx_buf,
+            &mut tx_meta,
+            &mut tx_buf,
+        );
+        // A source port other than 5353 also makes mDNS responders answer
+        // directly to us, like a unicast DNS server.
+        socket.bind(0).map_err(|_| DnsError::Socket)?;
+
+        // The ID only has to match request and response.
+        let id = Instant::now().as_ticks() as u16;
+        let mut message = [0u8; MAX_MESSAGE_SIZE];
+        let len = encode_query(&mut message, id, host)?;
+        socket
+            .send_to(&message[..len], server)
+            .await
+            .map_err(|_| DnsError::Socket)?;
+
+        let (len, _) = with_timeout(QUERY_TIMEOUT, socket.recv_from(&mut message))
+            .await
+            .map_err(|_| DnsError::Timeout)?
+            .map_err(|_| DnsError::Socket)?;
+        parse_response(&message[..len], id)
+    }
+}
+
+impl Dns for Resolver<'_> {
+    type Error = DnsError;
+
+    async fn get_host_by_name(
+        &self,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<IpAddr, Self::Error> {
+        if matches!(addr_type, AddrType::IPv6) {
+            return Err(DnsError::Unsupported);
+        }
+        if host.trim_end_matches('.').ends_with(".local") {
+            let group = IpEndpoint::new(MDNS_GROUP.into(), MDNS_PORT);
+            return self.query(group, host).await.map(IpAddr::V4);
+        }
+        if self.servers.is_empty() {
+            return self
+                .dhcp
+                .get_host_by_name(host, AddrType::IPv4)
+                .await
+                .map_err(DnsError::Dhcp);
+        }
+
+        let mut result = Err(DnsError::NotFound);
+        for server in self.servers {
+            result = self
+                .query(IpEndpoint::new((*server).into(), DNS_PORT), host)
+                .await;
+            match &result {
+                Ok(_) => break,
+                Err(e) => {
+                    defmt::warn!("DNS query to {} failed: {}", server, defmt::Debug2Format(e))
+                }
+            }
+        }
+        result.map(IpAddr::V4)
+    }
+
+    async fn get_host_by_address(
+        &self,
+        _addr: IpAddr,
+        _result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        Err(DnsError::Unsupported)
+    }
+}
+
+/// Writes a recursive A query for `host` into `message` and returns its length.
+fn encode_query(message: &mut [u8], id: u16, host: &str) -> Result<usize, DnsError> {
+    let host = host.trim_end_matches('.');
+    // Labels plus their length bytes, the root label and type/class.
+    if host.is_empty() || host.len() > 253 || 12 + host.len() + 2 + 4 > message.len() {
+        return Err(DnsError::InvalidName);
+    }
+
+    message[0..2].copy_from_slice(&id.to_be_bytes());
+    message[2..4].copy_from_slice(&0x0100u16.to_be_bytes()); // RD
+    message[4..6].copy_from_slice(&1u16.to_be_bytes()); // QDCOUNT
+    message[6..12].fill(0);
+
+    let mut pos = 12;
+    for label in host.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(DnsError::InvalidName);
+        }
+        message[pos] = label.len() as u8;
+        message[pos + 1..pos + 1 + label.len()].copy_from_slice(label.as_bytes());
+        pos += 1 + label.len();
+    }
+    message[pos] = 0;
+    pos += 1;
+    message[pos..pos + 2].copy_from_slice(&TYPE_A.to_be_bytes());
+    message[pos + 2..pos + 4].copy_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(pos + 4)
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Result<u16, DnsError> {
+    match message.get(pos..pos + 2) {
+        Some(&[hi, lo]) => Ok(u16::from_be_bytes([hi, lo])),
+        _ => Err(DnsError::Malformed),
+    }
+}
+
+/// Returns the position after the (possibly compressed) name at `pos`.
+fn skip_name(message: &[u8], mut pos: usize) -> Result<usize, DnsError> {
+    loop {
+        let len = *message.get(pos).ok_or(DnsError::Malformed)?;
+        match len {
+            0 => return Ok(pos + 1),
+            // A compression pointer ends the name.
+            _ if len & 0xC0 == 0xC0 => return Ok(pos + 2),
+            _ => pos += 1 + len as usize,
+        }
+    }
+}
+
+fn parse_response(message: &[u8], id: u16) -> Result<Ipv4Address, DnsError> {
+    if message.len() < 12 || read_u16(message, 0)? != id {
+        return Err(DnsError::Malformed);
+    }
+    if message[3] & 0x0F != 0 {
+        return Err(DnsError::NotFound);
+    }
+
+    let questions = read_u16(message, 4)?;
+    let answers = read_u16(message, 6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos)? + 4;
+    }
+    for _ in 0..answers {
+        pos = skip_name(message, pos)?;
+        let record_type = read_u16(message, pos)?;
+        let class = read_u16(message, pos + 2)?;
+        let data_len = read_u16(message, pos + 8)? as usize;
+        pos += 10;
+        let data = message
+            .get(pos..pos + data_len)
+            .ok_or(DnsError::Malformed)?;
+        if record_type == TYPE_A && class & CLASS_MASK == CLASS_IN && data_len == 4 {
+            return Ok(Ipv4Address::new(data[0], data[1], data[2], data[3]));
+        }
+        pos += data_len;
+    }
+    Err(DnsError::NotFound)
+}
@@ -0,0 +1,50 @@
+//! Seeds for the network stack and TLS connections.
+//!
+//! The hardware RNG draws on RF noise, which is only there while the radio
+//! runs, and the first seeds are taken right after boot. The pool therefore
+//! keeps folding in fresh RNG output and timer jitter from network events,
+//! and every TLS connection gets its own seed from it rather than reusing
+//! one seed for the whole uptime.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::Instant;
+use esp_hal::rng::Rng;
+
+static POOL: Mutex<CriticalSectionRawMutex, Cell<u64>> = Mutex::new(Cell::new(0));
+
+/// SplitMix64 finalizer: a bijection, so mixing never loses pool entropy.
+fn scramble(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Folds `sample` into the pool. Any source can contribute; weak samples
+/// don't hurt.
+pub fn mix(sample: u64) {
+    POOL.lock(|pool| pool.set(scramble(pool.get().rotate_left(17) ^ sample)));
+}
+
+/// Folds in the timer value, whose low bits jitter with the timing of
+/// network events.
+pub fn mix_timing() {
+    mix(Instant::now().as_ticks());
+}
+
+fn mix_hardware() {
+    let rng = Rng::new();
+    mix(rng.random() as u64 | ((rng.random() as u64) << 32));
+}
+
+/// A fresh seed, after mixing in new hardware RNG output.
+pub fn seed() -> u64 {
+    mix_hardware();
+    mix_timing();
+    let seed = POOL.lock(|pool| pool.get());
+    // Step the pool so the next seed differs even without new input.
+    mix(seed);
+    scramble(seed ^ 0x9E37_79B9_7F4A_7C15)
+}
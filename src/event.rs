@@ -0,0 +1,77 @@
+//! Collection categories and how every output presents them.
+
+use smart_leds::RGB8;
+use smart_leds::colors::{BLUE, BLUE_VIOLET, DEEP_PINK, GREEN, ORANGE, YELLOW};
+
+#[derive(defmt::Format, Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Event {
+    Verpackungs,
+    Bio,
+    Papier,
+    Restmüll,
+    Laubsack,
+    Weihnachtsbäume,
+}
+
+/// Presentation of an [`Event`], shared by the LED, log and text outputs so
+/// they don't each keep their own mapping.
+pub struct EventStyle {
+    /// LED color while a reminder for the event is active.
+    pub color: RGB8,
+    /// Compact name for logs and audit messages.
+    pub short_label: &'static str,
+    /// Names used in German and English reminder texts.
+    pub label_de: &'static str,
+    pub label_en: &'static str,
+}
+
+/// Indexed by the `Event` discriminant. Red, white, magenta and cyan are
+/// left to the status patterns of `status_led`, so a reminder can't be taken
+/// for a fault.
+const STYLES: [EventStyle; 6] = [
+    EventStyle {
+        color: YELLOW,
+        short_label: "Wertstoff",
+        label_de: "Wertstofftonne",
+        label_en: "recycling bin",
+    },
+    EventStyle {
+        // Brown comes out as dark red on the LED.
+        color: GREEN,
+        short_label: "Bio",
+        label_de: "Biotonne",
+        label_en: "organic waste bin",
+    },
+    EventStyle {
+        color: BLUE,
+        short_label: "Papier",
+        label_de: "Papiertonne",
+        label_en: "paper bin",
+    },
+    EventStyle {
+        // The bin is black, which the LED can't show.
+        color: BLUE_VIOLET,
+        short_label: "Restmüll",
+        label_de: "Restmülltonne",
+        label_en: "residual waste bin",
+    },
+    EventStyle {
+        color: ORANGE,
+        short_label: "Laub",
+        label_de: "Laubsäcke",
+        label_en: "leaf bags",
+    },
+    EventStyle {
+        color: DEEP_PINK,
+        short_label: "Weihnachtsbaum",
+        label_de: "Weihnachtsbäume",
+        label_en: "Christmas trees",
+    },
+];
+
+impl Event {
+    pub fn style(self) -> &'static EventStyle {
+        &STYLES[self as usize]
+    }
+}
@@ -0,0 +1,135 @@
+//! HTTP(S) transport for calendar sources.
+//!
+//! Sources fetch through [`HttpFetch`] rather than through reqwless
+//! directly, so they can share one client and tests or other transports can
+//! stand in for it.
+
+use embassy_net::tcp::client::{TcpClient, TcpClientState};
+use embassy_net::{Ipv4Address, Stack};
+use reqwless::client::{HttpClient, TlsConfig, TlsVerify};
+use reqwless::headers::ContentType;
+use reqwless::request::{Method, RequestBuilder};
+
+use crate::config::CONFIG;
+use crate::dns::Resolver;
+use crate::profile::{self, Phase};
+use crate::{entropy, heap};
+
+const RX_BUFFER_SIZE: usize = CONFIG.rx_buffer_size;
+const TX_BUFFER_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub enum FetchError {
+    Http(reqwless::Error),
+    /// The server answered with a status other than 2xx.
+    Status(u16),
+    /// The body has no VEVENT at all. An empty calendar is more likely a
+    /// login or error page than a real one.
+    NoEvents,
+    InvalidUtf8,
+}
+
+impl From<reqwless::Error> for FetchError {
+    fn from(e: reqwless::Error) -> Self {
+        FetchError::Http(e)
+    }
+}
+
+#[allow(async_fn_in_trait)]
+pub trait HttpFetch {
+    /// GETs `url` into `buffer` and returns the body as a slice of it. Error
+    /// pages, i.e. any status other than 2xx, are an error.
+    async fn get<'b>(&mut self, url: &str, buffer: &'b mut [u8]) -> Result<&'b [u8], FetchError>;
+
+    /// Like [`get`](Self::get), for bodies that must be UTF-8.
+    async fn get_text<'b>(
+        &mut self,
+        url: &str,
+        buffer: &'b mut [u8],
+    ) -> Result<&'b str, FetchError> {
+        let body = self.get(url, buffer).await?;
+        core::str::from_utf8(body).map_err(|_| FetchError::InvalidUtf8)
+    }
+
+    /// POSTs `body` as JSON to `url` and returns the response status code.
+    async fn post_json(&mut self, url: &str, body: &[u8]) -> Result<u16, FetchError>;
+}
+
+/// reqwless over the embassy-net stack, for `http://` and `https://` URLs.
+pub struct ReqwlessFetch<'a> {
+    stack: Stack<'a>,
+    dns_servers: &'a [Ipv4Address],
+}
+
+impl<'a> ReqwlessFetch<'a> {
+    pub fn new(stack: Stack<'a>, dns_servers: &'a [Ipv4Address]) -> Self {
+        ReqwlessFetch { stack, dns_servers }
+    }
+}
+
+impl HttpFetch for ReqwlessFetch<'_> {
+    async fn get<'b>(&mut self, url: &str, buffer: &'b mut [u8]) -> Result<&'b [u8], FetchError> {
+        let mut rx_buffer = [0; RX_BUFFER_SIZE];
+        let mut tx_buffer = [0; TX_BUFFER_SIZE];
+        let dns = Resolver::new(self.stack, self.dns_servers);
+        let tcp_state = TcpClientState::<1, TX_BUFFER_SIZE, RX_BUFFER_SIZE>::new();
+        let tcp = TcpClient::new(self.stack, &tcp_state);
+
+        let tls = TlsConfig::new(
+            entropy::seed(),
+            &mut rx_buffer,
+            &mut tx_buffer,
+            TlsVerify::None,
+        );
+
+        let mut client = HttpClient::new_with_tls(&tcp, &dns, tls);
+        heap::checkpoint("before TLS handshake");
+        let handshake = profile::begin(Phase::TlsHandshake);
+        let mut http_req = client.request(Method::GET, url).await?;
+        drop(handshake);
+        heap::checkpoint("after TLS handshake");
+        let _fetch = profile::begin(Phase::Fetch);
+        defmt::info!("requesting");
+        let response = http_req.send(buffer).await?;
+
+        defmt::info!("Got response, status {}", response.status.0);
+        if !(200..300).contains(&response.status.0) {
+            return Err(FetchError::Status(response.status.0));
+        }
+        Ok(response.body().read_to_end().await?)
+    }
+
+    async fn post_json(&mut self, url: &str, body: &[u8]) -> Result<u16, FetchError> {
+        let mut rx_buffer = [0; RX_BUFFER_SIZE];
+        let mut tx_buffer = [0; TX_BUFFER_SIZE];
+        let dns = Resolver::new(self.stack, self.dns_servers);
+        let tcp_state = TcpClientState::<1, TX_BUFFER_SIZE, RX_BUFFER_SIZE>::new();
+        let tcp = TcpClient::new(self.stack, &tcp_state);
+
+        let tls = TlsConfig::new(
+            entropy::seed(),
+            &mut rx_buffer,
+            &mut tx_buffer,
+            TlsVerify::None,
+        );
+
+        let mut client = HttpClient::new_with_tls(&tcp, &dns, tls);
+        let mut response_buffer = [0; 512];
+        let response = client
+            .request(Method::POST, url)
+            .await?
+            .body(body)
+            .content_type(ContentType::ApplicationJson)
+            .send(&mut response_buffer)
+            .await?;
+        Ok(response.status.0)
+    }
+}
+
+/// Host of `url`, without scheme, port, path or query. The path may identify
+/// the household, so only the host goes into logs.
+pub fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?']).next().unwrap_or(rest);
+    authority.split(':').next().unwrap_or(authority)
+}
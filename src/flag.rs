@@ -0,0 +1,68 @@
+//! Optional mechanical flag on a hobby servo, raised while bins are due.
+//!
+//! The servo is driven by a 50 Hz LEDC PWM signal whose pulse width sets the
+//! angle. [`set_raised`] is called by [`crate::status_led`] whenever bins
+//! become due or the reminder ends or is acknowledged.
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use esp_hal::gpio::DriveMode;
+use esp_hal::gpio::interconnect::PeripheralOutput;
+use esp_hal::ledc::channel::{self, ChannelHW, ChannelIFace};
+use esp_hal::ledc::timer::{self, TimerIFace};
+use esp_hal::ledc::{LSGlobalClkSource, Ledc, LowSpeed};
+use esp_hal::peripherals::LEDC;
+use esp_hal::time::Rate;
+
+use crate::config::FlagServo;
+
+const PERIOD_US: u32 = 20_000;
+const DUTY_BITS: u32 = 14;
+/// Pulse widths for 0 and 180 degrees on common micro servos.
+const MIN_PULSE_US: u32 = 500;
+const MAX_PULSE_US: u32 = 2500;
+
+static RAISED: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+
+pub fn set_raised(raised: bool) {
+    RAISED.signal(raised);
+}
+
+fn duty(angle: u8) -> u32 {
+    let pulse_us = MIN_PULSE_US + (MAX_PULSE_US - MIN_PULSE_US) * angle.min(180) as u32 / 180;
+    pulse_us * (1 << DUTY_BITS) / PERIOD_US
+}
+
+/// Drives the servo on `pin` forever, starting lowered.
+pub async fn run(ledc: LEDC<'static>, pin: impl PeripheralOutput<'static>, servo: FlagServo) -> ! {
+    let mut ledc = Ledc::new(ledc);
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+    let mut timer = ledc.timer::<LowSpeed>(timer::Number::Timer0);
+    timer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty14Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: Rate::from_hz(1_000_000 / PERIOD_US),
+        })
+        .expect("Failed to configure the servo timer");
+    let mut channel = ledc.channel(channel::Number::Channel0, pin);
+    channel
+        .configure(channel::config::Config {
+            timer: &timer,
+            duty_pct: 0,
+            drive_mode: DriveMode::PushPull,
+        })
+        .expect("Failed to configure the servo channel");
+
+    channel.set_duty_hw(duty(servo.lowered_angle));
+    loop {
+        let raised = RAISED.wait().await;
+        defmt::info!("Flag {}", if raised { "raised" } else { "lowered" });
+        let angle = if raised {
+            servo.raised_angle
+        } else {
+            servo.lowered_angle
+        };
+        channel.set_duty_hw(duty(angle));
+    }
+}
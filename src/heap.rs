@@ -0,0 +1,21 @@
+//! Heap usage checkpoints.
+//!
+//! Logged around the memory hungry phases (WiFi init, TLS handshake, ICS
+//! parsing) to show how much of the heap they really need. The low-water
+//! mark is the least free heap seen at any checkpoint so far.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+static LOW_WATER: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+pub fn checkpoint(label: &str) {
+    let free = esp_alloc::HEAP.free();
+    let low_water = LOW_WATER.fetch_min(free, Ordering::Relaxed).min(free);
+    defmt::info!(
+        "Heap {}: {} bytes free, {} used, low water {} free",
+        label,
+        free,
+        esp_alloc::HEAP.used(),
+        low_water
+    );
+}
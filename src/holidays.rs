@@ -0,0 +1,204 @@
+//! German public holidays per federal state.
+//!
+//! Collections are normally moved when they fall on a public holiday. A pickup
+//! date that still lands on one is suspicious and is flagged for verification
+//! rather than announced with confidence.
+
+use time::{Date, Duration, Month, Weekday};
+
+#[derive(defmt::Format, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Region {
+    BadenWuerttemberg,
+    Bayern,
+    Berlin,
+    Brandenburg,
+    Bremen,
+    Hamburg,
+    Hessen,
+    MecklenburgVorpommern,
+    Niedersachsen,
+    NordrheinWestfalen,
+    RheinlandPfalz,
+    Saarland,
+    Sachsen,
+    SachsenAnhalt,
+    SchleswigHolstein,
+    Thueringen,
+}
+
+enum Rule {
+    Fixed(Month, u8),
+    /// Days relative to Easter Sunday.
+    Easter(i64),
+    /// Buß- und Bettag, the Wednesday before November 23.
+    RepentanceDay,
+}
+
+struct Holiday {
+    name: &'static str,
+    rule: Rule,
+    /// `None` for nationwide holidays.
+    regions: Option<&'static [Region]>,
+}
+
+const HOLIDAYS: &[Holiday] = {
+    use Region::*;
+    &[
+        Holiday {
+            name: "Neujahr",
+            rule: Rule::Fixed(Month::January, 1),
+            regions: None,
+        },
+        Holiday {
+            name: "Heilige Drei Könige",
+            rule: Rule::Fixed(Month::January, 6),
+            regions: Some(&[BadenWuerttemberg, Bayern, SachsenAnhalt]),
+        },
+        Holiday {
+            name: "Internationaler Frauentag",
+            rule: Rule::Fixed(Month::March, 8),
+            regions: Some(&[Berlin, MecklenburgVorpommern]),
+        },
+        Holiday {
+            name: "Karfreitag",
+            rule: Rule::Easter(-2),
+            regions: None,
+        },
+        Holiday {
+            name: "Ostermontag",
+            rule: Rule::Easter(1),
+            regions: None,
+        },
+        Holiday {
+            name: "Tag der Arbeit",
+            rule: Rule::Fixed(Month::May, 1),
+            regions: None,
+        },
+        Holiday {
+            name: "Christi Himmelfahrt",
+            rule: Rule::Easter(39),
+            regions: None,
+        },
+        Holiday {
+            name: "Pfingstmontag",
+            rule: Rule::Easter(50),
+            regions: None,
+        },
+        Holiday {
+            name: "Fronleichnam",
+            rule: Rule::Easter(60),
+            regions: Some(&[
+                BadenWuerttemberg,
+                Bayern,
+                Hessen,
+                NordrheinWestfalen,
+                RheinlandPfalz,
+                Saarland,
+            ]),
+        },
+        Holiday {
+            name: "Mariä Himmelfahrt",
+            rule: Rule::Fixed(Month::August, 15),
+            regions: Some(&[Saarland]),
+        },
+        Holiday {
+            name: "Weltkindertag",
+            rule: Rule::Fixed(Month::September, 20),
+            regions: Some(&[Thueringen]),
+        },
+        Holiday {
+            name: "Tag der Deutschen Einheit",
+            rule: Rule::Fixed(Month::October, 3),
+            regions: None,
+        },
+        Holiday {
+            name: "Reformationstag",
+            rule: Rule::Fixed(Month::October, 31),
+            regions: Some(&[
+                Brandenburg,
+                Bremen,
+                Hamburg,
+                MecklenburgVorpommern,
+                Niedersachsen,
+                Sachsen,
+                SachsenAnhalt,
+                SchleswigHolstein,
+                Thueringen,
+            ]),
+        },
+        Holiday {
+            name: "Allerheiligen",
+            rule: Rule::Fixed(Month::November, 1),
+            regions: Some(&[
+                BadenWuerttemberg,
+                Bayern,
+                NordrheinWestfalen,
+                RheinlandPfalz,
+                Saarland,
+            ]),
+        },
+        Holiday {
+            name: "Buß- und Bettag",
+            rule: Rule::RepentanceDay,
+            regions: Some(&[Sachsen]),
+        },
+        Holiday {
+            name: "1. Weihnachtstag",
+            rule: Rule::Fixed(Month::December, 25),
+            regions: None,
+        },
+        Holiday {
+            name: "2. Weihnachtstag",
+            rule: Rule::Fixed(Month::December, 26),
+            regions: None,
+        },
+    ]
+};
+
+/// Easter Sunday in the Gregorian calendar (anonymous Gregorian algorithm).
+fn easter_sunday(year: i32) -> Option<Date> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+
+    let month = Month::try_from(month as u8).ok()?;
+    Date::from_calendar_date(year, month, day as u8).ok()
+}
+
+fn repentance_day(year: i32) -> Option<Date> {
+    let mut date = Date::from_calendar_date(year, Month::November, 22).ok()?;
+    while date.weekday() != Weekday::Wednesday {
+        date = date.previous_day()?;
+    }
+    Some(date)
+}
+
+impl Rule {
+    fn date(&self, year: i32) -> Option<Date> {
+        match *self {
+            Rule::Fixed(month, day) => Date::from_calendar_date(year, month, day).ok(),
+            Rule::Easter(offset) => easter_sunday(year)?.checked_add(Duration::days(offset)),
+            Rule::RepentanceDay => repentance_day(year),
+        }
+    }
+}
+
+/// Returns the name of the public holiday on `date` in `region`, if any.
+pub fn holiday_name(region: Region, date: Date) -> Option<&'static str> {
+    HOLIDAYS
+        .iter()
+        .filter(|h| h.regions.is_none_or(|regions| regions.contains(&region)))
+        .find(|h| h.rule.date(date.year()) == Some(date))
+        .map(|h| h.name)
+}
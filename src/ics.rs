@@ -0,0 +1,834 @@
+//! Parser for the collection calendar ICS feed.
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::iter::Peekable;
+use core::str::Lines;
+use time::{Date, Duration, Month, PrimitiveDateTime, Time, Weekday};
+
+use crate::clock;
+use crate::event::Event;
+
+#[derive(Debug)]
+pub struct IcsEvent {
+    pub dtstart: Option<Date>,
+    pub event_type: Option<Event>,
+}
+
+/// Identity of a collection across refreshes. ICS UIDs are not stable with
+/// every backend, the date and the bin are.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EventId {
+    pub date: Date,
+    pub event: Event,
+}
+
+/// Limits which parsed events are kept: only those from `today` up to
+/// `horizon_days` ahead, and at most `max_events` of them, so a huge or
+/// malicious feed can't exhaust the heap.
+#[derive(Debug, Clone, Copy)]
+pub struct EventWindow {
+    /// `None` while the clock is not set; then only `max_events` applies.
+    pub today: Option<Date>,
+    pub horizon_days: u16,
+    pub max_events: usize,
+}
+
+impl EventWindow {
+    fn contains(&self, date: Date) -> bool {
+        self.today.is_none_or(|today| {
+            date >= today && (date - today).whole_days() <= self.horizon_days as i64
+        })
+    }
+}
+
+/// Number of distinct unknown summaries kept as samples in a [`ParseReport`].
+pub const MAX_UNKNOWN_SUMMARY_SAMPLES: usize = 4;
+
+/// What the parser skipped while reading a feed, so that format changes are
+/// noticed instead of silently dropping events.
+#[derive(Debug, Default)]
+pub struct ParseReport {
+    pub events: usize,
+    /// All VEVENTs in the document, whether they produced an event or not.
+    pub vevents: usize,
+    /// VEVENTs that did not produce an event (unknown summary, missing or
+    /// unusable DTSTART).
+    pub dropped_events: usize,
+    pub unknown_summaries: usize,
+    pub unknown_summary_samples: Vec<String>,
+    pub invalid_dtstarts: usize,
+    /// Components other than VEVENT/VTIMEZONE, e.g. VALARM or VTODO.
+    pub ignored_components: usize,
+    /// Lines that are not valid content lines.
+    pub skipped_lines: usize,
+    /// Events in the past or beyond the horizon of the [`EventWindow`].
+    pub outside_window: usize,
+    /// Events dropped because `max_events` was reached.
+    pub evicted: usize,
+}
+
+impl ParseReport {
+    fn record_unknown_summary(&mut self, summary: &str) {
+        self.unknown_summaries += 1;
+        if self.unknown_summary_samples.len() < MAX_UNKNOWN_SUMMARY_SAMPLES
+            && !self.unknown_summary_samples.iter().any(|s| s == summary)
+        {
+            self.unknown_summary_samples.push(String::from(summary));
+        }
+    }
+
+    pub fn log(&self) {
+        defmt::info!(
+            "Parsed {} events from {} VEVENTs ({} outside window, {} evicted, {} dropped, {} unknown summaries, {} invalid DTSTARTs, {} ignored components, {} skipped lines)",
+            self.events,
+            self.vevents,
+            self.outside_window,
+            self.evicted,
+            self.dropped_events,
+            self.unknown_summaries,
+            self.invalid_dtstarts,
+            self.ignored_components,
+            self.skipped_lines
+        );
+        for sample in &self.unknown_summary_samples {
+            defmt::warn!("Unknown summary: {}", sample.as_str());
+        }
+    }
+}
+
+pub fn parse_yyyymmdd(s: &str) -> Result<Date, &'static str> {
+    if s.len() != 8 || !s.is_ascii() {
+        return Err("Expected 8 characters (YYYYMMDD)");
+    }
+
+    let year = s[0..4].parse::<i32>().map_err(|_| "Invalid year")?;
+    let month_num = s[4..6].parse::<u8>().map_err(|_| "Invalid month")?;
+    let day = s[6..8].parse::<u8>().map_err(|_| "Invalid day")?;
+
+    let month = Month::try_from(month_num).map_err(|_| "Invalid month")?;
+
+    Date::from_calendar_date(year, month, day).map_err(|_| "Invalid date")
+}
+
+/// Parses a DATE-TIME value (`YYYYMMDDTHHMMSS`, optionally with a trailing
+/// `Z`). Returns the wall-clock time and whether it is in UTC.
+fn parse_date_time(s: &str) -> Result<(PrimitiveDateTime, bool), &'static str> {
+    let (s, utc) = match s.strip_suffix('Z') {
+        Some(s) => (s, true),
+        None => (s, false),
+    };
+    if s.len() != 15 || !s.is_ascii() || s.as_bytes()[8] != b'T' {
+        return Err("Expected YYYYMMDDTHHMMSS");
+    }
+
+    let date = parse_yyyymmdd(&s[0..8])?;
+    let hour = s[9..11].parse::<u8>().map_err(|_| "Invalid hour")?;
+    let minute = s[11..13].parse::<u8>().map_err(|_| "Invalid minute")?;
+    let second = s[13..15].parse::<u8>().map_err(|_| "Invalid second")?;
+    let time = Time::from_hms(hour, minute, second).map_err(|_| "Invalid time")?;
+
+    Ok((PrimitiveDateTime::new(date, time), utc))
+}
+
+/// Parses a UTC offset such as `+0100` or `-053000` into seconds.
+fn parse_utc_offset(s: &str) -> Result<i32, &'static str> {
+    let sign = match s.as_bytes().first() {
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => return Err("Expected UTC offset sign"),
+    };
+    let digits = &s[1..];
+    if (digits.len() != 4 && digits.len() != 6) || !digits.is_ascii() {
+        return Err("Expected HHMM or HHMMSS UTC offset");
+    }
+
+    let hours = digits[0..2].parse::<i32>().map_err(|_| "Invalid offset")?;
+    let minutes = digits[2..4].parse::<i32>().map_err(|_| "Invalid offset")?;
+    let seconds = match digits.get(4..6) {
+        Some(ss) => ss.parse::<i32>().map_err(|_| "Invalid offset")?,
+        None => 0,
+    };
+
+    Ok(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// A content line split into its name, raw parameter list and value.
+struct Property<'a> {
+    name: &'a str,
+    params: &'a str,
+    value: &'a str,
+}
+
+impl<'a> Property<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        // The value starts at the first colon that is not inside a quoted
+        // parameter value.
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(i),
+            _ => None,
+        })?;
+
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+        let (name, params) = head.split_once(';').unwrap_or((head, ""));
+        Some(Property {
+            name,
+            params,
+            value,
+        })
+    }
+
+    /// The decoded value of a TEXT property such as SUMMARY.
+    fn text(&self) -> Cow<'a, str> {
+        let value = if self
+            .param("ENCODING")
+            .is_some_and(|e| e.eq_ignore_ascii_case("QUOTED-PRINTABLE"))
+        {
+            let latin1 = self
+                .param("CHARSET")
+                .is_some_and(|c| c.eq_ignore_ascii_case("ISO-8859-1"));
+            Cow::Owned(decode_quoted_printable(self.value, latin1))
+        } else {
+            Cow::Borrowed(self.value)
+        };
+
+        match value {
+            Cow::Borrowed(value) => unescape_text(value),
+            Cow::Owned(value) => Cow::Owned(unescape_text(&value).into_owned()),
+        }
+    }
+
+    fn param(&self, key: &str) -> Option<&'a str> {
+        self.params
+            .split(';')
+            .filter_map(|param| param.split_once('='))
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.trim_matches('"'))
+    }
+}
+
+/// Joins folded content lines (RFC 5545 3.1): a line starting with a space or
+/// tab continues the previous one. Quoted-printable values from vCalendar 1.0
+/// exporters use a trailing `=` as soft line break instead, which is joined
+/// as well. Unfolded lines are only allocated when folding actually occurs.
+struct Unfold<'a> {
+    lines: Peekable<Lines<'a>>,
+}
+
+fn is_quoted_printable_soft_break(line: &str) -> bool {
+    line.ends_with('=')
+        && Property::parse(line).is_some_and(|property| {
+            property
+                .param("ENCODING")
+                .is_some_and(|e| e.eq_ignore_ascii_case("QUOTED-PRINTABLE"))
+        })
+}
+
+impl<'a> Iterator for Unfold<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.lines.next()?;
+        let folded = self
+            .lines
+            .peek()
+            .is_some_and(|next| next.starts_with([' ', '\t']));
+        let soft_break = is_quoted_printable_soft_break(first);
+        if !folded && !soft_break {
+            return Some(Cow::Borrowed(first));
+        }
+
+        let mut line = String::from(first);
+        while let Some(next) = self.lines.next_if(|next| next.starts_with([' ', '\t'])) {
+            line.push_str(&next[1..]);
+        }
+        if soft_break {
+            while line.ends_with('=') {
+                line.pop();
+                match self.lines.next() {
+                    Some(next) => line.push_str(next),
+                    None => break,
+                }
+            }
+        }
+        Some(Cow::Owned(line))
+    }
+}
+
+fn unfold(ics_document: &str) -> Unfold<'_> {
+    Unfold {
+        lines: ics_document.lines().peekable(),
+    }
+}
+
+/// Undoes RFC 5545 TEXT escaping: `\\`, `\;`, `\,` and `\n`/`\N`. Invalid
+/// escapes are kept verbatim.
+pub fn unescape_text(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(escaped @ ('\\' | ';' | ',')) => out.push(escaped),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    Cow::Owned(out)
+}
+
+fn hex_digit(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes `=XX` sequences of a quoted-printable value. Malformed sequences
+/// are kept verbatim. `latin1` selects ISO-8859-1 instead of UTF-8 for the
+/// decoded bytes.
+fn decode_quoted_printable(value: &str, latin1: bool) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'='
+            && let Some(&[hi, lo]) = bytes.get(i + 1..i + 3)
+            && let (Some(hi), Some(lo)) = (hex_digit(hi), hex_digit(lo))
+        {
+            decoded.push((hi << 4) | lo);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    if latin1 {
+        decoded.iter().map(|&b| b as char).collect()
+    } else {
+        String::from_utf8_lossy(&decoded).into_owned()
+    }
+}
+
+/// `FREQ=YEARLY` recurrence of a timezone observance, e.g. the last Sunday
+/// in October.
+#[derive(Debug, Clone, Copy)]
+struct YearlyRule {
+    month: Month,
+    /// 1-based week of the month, negative counts from the end.
+    week: i8,
+    weekday: Weekday,
+    /// Last onset allowed by `UNTIL`, and whether it is in UTC rather than
+    /// local time.
+    until: Option<(PrimitiveDateTime, bool)>,
+    /// Number of onsets allowed by `COUNT`, one per year from DTSTART on.
+    count: Option<u32>,
+}
+
+impl YearlyRule {
+    fn parse(rrule: &str) -> Option<Self> {
+        let mut yearly = false;
+        let mut month = None;
+        let mut by_day = None;
+        let mut until = None;
+        let mut count = None;
+        for part in rrule.split(';') {
+            match part.split_once('=')? {
+                ("FREQ", freq) => yearly = freq == "YEARLY",
+                ("BYMONTH", m) => month = Month::try_from(m.parse::<u8>().ok()?).ok(),
+                ("BYDAY", d) => by_day = Some(d),
+                // A DATE covers the whole day.
+                ("UNTIL", u) if u.len() == 8 => {
+                    until = Some((parse_yyyymmdd(u).ok()?.with_hms(23, 59, 59).ok()?, false))
+                }
+                ("UNTIL", u) => until = Some(parse_date_time(u).ok()?),
+                ("COUNT", c) => count = Some(c.parse().ok()?),
+                _ => {}
+            }
+        }
+        if !yearly {
+            return None;
+        }
+
+        let by_day = by_day?;
+        let split = by_day.len().checked_sub(2)?;
+        let (week, day) = (by_day.get(..split)?, by_day.get(split..)?);
+        let weekday = match day {
+            "MO" => Weekday::Monday,
+            "TU" => Weekday::Tuesday,
+            "WE" => Weekday::Wednesday,
+            "TH" => Weekday::Thursday,
+            "FR" => Weekday::Friday,
+            "SA" => Weekday::Saturday,
+            "SU" => Weekday::Sunday,
+            _ => return None,
+        };
+        Some(YearlyRule {
+            month: month?,
+            week: week.trim_start_matches('+').parse().ok()?,
+            weekday,
+            until,
+            count,
+        })
+    }
+
+    fn date(&self, year: i32) -> Option<Date> {
+        if self.week > 0 {
+            let mut date = Date::from_calendar_date(year, self.month, 1).ok()?;
+            while date.weekday() != self.weekday {
+                date = date.next_day()?;
+            }
+            let date = date.checked_add(Duration::weeks(self.week as i64 - 1))?;
+            (date.month() == self.month).then_some(date)
+        } else {
+            let mut date = Date::from_calendar_date(year, self.month, 28).ok()?;
+            while date.next_day()?.month() == self.month {
+                date = date.next_day()?;
+            }
+            while date.weekday() != self.weekday {
+                date = date.previous_day()?;
+            }
+            let date = date.checked_sub(Duration::weeks(-(self.week as i64) - 1))?;
+            (date.month() == self.month).then_some(date)
+        }
+    }
+}
+
+/// A STANDARD or DAYLIGHT block of a VTIMEZONE.
+#[derive(Debug)]
+struct Observance {
+    /// Local time of the first onset.
+    start: PrimitiveDateTime,
+    offset_from: i32,
+    offset_to: i32,
+    rule: Option<YearlyRule>,
+}
+
+impl Observance {
+    /// Onsets in `year` and the year before, so that a date in January still
+    /// finds last October's transition.
+    fn onsets_around(&self, year: i32) -> [Option<PrimitiveDateTime>; 2] {
+        match self.rule {
+            Some(rule) => [year - 1, year].map(|y| {
+                rule.date(y)
+                    .map(|date| PrimitiveDateTime::new(date, self.start.time()))
+                    .filter(|onset| *onset >= self.start && self.within_limits(&rule, *onset))
+            }),
+            None => [Some(self.start), None],
+        }
+    }
+
+    /// Whether `onset` comes before the end set by the rule's UNTIL or COUNT.
+    fn within_limits(&self, rule: &YearlyRule, onset: PrimitiveDateTime) -> bool {
+        let before_until = rule.until.is_none_or(|(until, utc)| {
+            // Onsets are in the local time of the offset they change from.
+            let onset = if utc {
+                onset.checked_sub(Duration::seconds(self.offset_from as i64))
+            } else {
+                Some(onset)
+            };
+            onset.is_some_and(|onset| onset <= until)
+        });
+        let within_count = rule
+            .count
+            .is_none_or(|count| ((onset.year() - self.start.year()) as u32) < count);
+        before_until && within_count
+    }
+}
+
+#[derive(Default)]
+struct ObservanceBuilder {
+    start: Option<PrimitiveDateTime>,
+    offset_from: Option<i32>,
+    offset_to: Option<i32>,
+    rule: Option<YearlyRule>,
+}
+
+impl ObservanceBuilder {
+    fn apply(&mut self, property: &Property<'_>) {
+        match property.name {
+            "DTSTART" => self.start = parse_date_time(property.value).ok().map(|t| t.0),
+            "TZOFFSETFROM" => self.offset_from = parse_utc_offset(property.value).ok(),
+            "TZOFFSETTO" => self.offset_to = parse_utc_offset(property.value).ok(),
+            "RRULE" => self.rule = YearlyRule::parse(property.value),
+            _ => {}
+        }
+    }
+
+    fn build(self) -> Option<Observance> {
+        Some(Observance {
+            start: self.start?,
+            offset_from: self.offset_from?,
+            offset_to: self.offset_to?,
+            rule: self.rule,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct TimeZone {
+    tzid: String,
+    observances: Vec<Observance>,
+}
+
+impl TimeZone {
+    /// UTC offset in seconds in effect at the local time `local`.
+    fn offset_at(&self, local: PrimitiveDateTime) -> Option<i32> {
+        let mut latest: Option<(PrimitiveDateTime, i32)> = None;
+        for observance in &self.observances {
+            for onset in observance.onsets_around(local.year()).into_iter().flatten() {
+                if onset <= local && latest.is_none_or(|(previous, _)| onset > previous) {
+                    latest = Some((onset, observance.offset_to));
+                }
+            }
+        }
+        // Before the first onset the zone is still on the offset it came from.
+        latest
+            .map(|(_, offset)| offset)
+            .or_else(|| self.observances.first().map(|o| o.offset_from))
+    }
+}
+
+/// Resolves a DTSTART to the collection date.
+///
+/// DATE values are taken as they are. DATE-TIME values in UTC, or with the
+/// TZID of a VTIMEZONE, are converted to the device's local date, which is
+/// what reminders compare against. Times with an unknown TZID are treated as
+/// floating.
+fn parse_dtstart(property: &Property<'_>, timezones: &[TimeZone]) -> Result<Date, &'static str> {
+    if property.param("VALUE") == Some("DATE") || property.value.len() == 8 {
+        return parse_yyyymmdd(property.value);
+    }
+
+    let (local, utc) = parse_date_time(property.value)?;
+    let offset = if utc {
+        0
+    } else {
+        let timezone = property
+            .param("TZID")
+            .and_then(|tzid| timezones.iter().find(|tz| tz.tzid == tzid));
+        match timezone {
+            Some(tz) => tz.offset_at(local).ok_or("VTIMEZONE without observances")?,
+            None => return Ok(local.date()),
+        }
+    };
+    let unix_time = local.assume_utc().unix_timestamp() - offset as i64;
+    clock::checked_local_date(unix_time).ok_or("DTSTART out of range")
+}
+
+fn event_for_summary(summary: &str) -> Option<Event> {
+    match summary {
+        "Abfuhr gelbe Wertstofftonne/-sack" => Some(Event::Verpackungs),
+        "Abfuhr grüne Biotonne" => Some(Event::Bio),
+        "Abfuhr blaue Papiertonne" => Some(Event::Papier),
+        "Abfuhr schwarze Restmülltonne" => Some(Event::Restmüll),
+        "Abfuhr Laubsäcke" => Some(Event::Laubsack),
+        "Abfuhr Weihnachtsbäume" => Some(Event::Weihnachtsbäume),
+        _ => None,
+    }
+}
+
+pub fn extract_ics_event(ics_document: &str, window: &EventWindow) -> (Vec<IcsEvent>, ParseReport) {
+    let mut ics_events: Vec<IcsEvent> = Vec::with_capacity(window.max_events);
+    let mut report = ParseReport::default();
+    let mut timezones: Vec<TimeZone> = Vec::new();
+    let mut timezone: Option<TimeZone> = None;
+    let mut observance: Option<ObservanceBuilder> = None;
+    // Nesting depth inside a component we don't interpret.
+    let mut ignored_depth: usize = 0;
+    let mut event_type: Option<Event> = None;
+    let mut start_ts: Option<Date> = None;
+
+    for line_str in unfold(ics_document) {
+        let line = line_str.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let Some(property) = Property::parse(line) else {
+            report.skipped_lines += 1;
+            continue;
+        };
+
+        if ignored_depth > 0 {
+            match property.name {
+                "BEGIN" => ignored_depth += 1,
+                "END" => ignored_depth -= 1,
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Some(builder) = observance.as_mut()
+            && property.name != "END"
+        {
+            builder.apply(&property);
+            continue;
+        }
+
+        match (property.name, property.value) {
+            ("BEGIN" | "END", "VCALENDAR") => {}
+            ("BEGIN", "VTIMEZONE") => {
+                timezone = Some(TimeZone {
+                    tzid: String::new(),
+                    observances: Vec::new(),
+                });
+            }
+            ("BEGIN", "STANDARD" | "DAYLIGHT") => observance = Some(ObservanceBuilder::default()),
+            ("END", "STANDARD" | "DAYLIGHT") => {
+                let built = observance.take().and_then(ObservanceBuilder::build);
+                if let (Some(tz), Some(built)) = (timezone.as_mut(), built) {
+                    tz.observances.push(built);
+                }
+            }
+            ("END", "VTIMEZONE") => timezones.extend(timezone.take()),
+            ("BEGIN", "VEVENT") => {
+                report.vevents += 1;
+                event_type = None;
+                start_ts = None;
+            }
+            ("END", "VEVENT") => {
+                let (Some(date), Some(_)) = (start_ts, event_type) else {
+                    report.dropped_events += 1;
+                    continue;
+                };
+                let event = IcsEvent {
+                    dtstart: start_ts,
+                    event_type,
+                };
+
+                if !window.contains(date) {
+                    report.outside_window += 1;
+                } else if ics_events.len() < window.max_events {
+                    ics_events.push(event);
+                } else {
+                    // Keep the nearest events: the new one replaces the
+                    // latest stored event if it is earlier.
+                    report.evicted += 1;
+                    let latest = ics_events
+                        .iter()
+                        .enumerate()
+                        .max_by_key(|(_, e)| e.dtstart)
+                        .map(|(i, _)| i);
+                    if let Some(i) = latest
+                        && ics_events[i].dtstart > event.dtstart
+                    {
+                        ics_events.swap_remove(i);
+                        ics_events.push(event);
+                    }
+                }
+            }
+            ("BEGIN", _) => {
+                report.ignored_components += 1;
+                ignored_depth = 1;
+            }
+            ("TZID", tzid) => {
+                if let Some(tz) = timezone.as_mut() {
+                    tz.tzid = String::from(tzid);
+                }
+            }
+            ("DTSTART", _) => match parse_dtstart(&property, &timezones) {
+                Ok(date) => start_ts = Some(date),
+                Err(e) => {
+                    defmt::warn!("Invalid DTSTART {}: {}", property.value, e);
+                    report.invalid_dtstarts += 1;
+                }
+            },
+            ("SUMMARY", _) => {
+                let text = property.text();
+                let summary = text.trim();
+                event_type = event_for_summary(summary);
+                if event_type.is_none() {
+                    report.record_unknown_summary(summary);
+                }
+            }
+            _ => {}
+        }
+    }
+    report.events = ics_events.len();
+    (ics_events, report)
+}
+
+/// The distinct bins collected on `date`, in feed order.
+pub fn bins_on(events: &[IcsEvent], date: Date) -> Vec<Event> {
+    let mut bins = Vec::new();
+    for event in events {
+        if event.dtstart == Some(date)
+            && let Some(bin) = event.event_type
+            && !bins.contains(&bin)
+        {
+            bins.push(bin);
+        }
+    }
+    bins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    const WINDOW: EventWindow = EventWindow {
+        today: None,
+        horizon_days: 56,
+        max_events: 64,
+    };
+
+    fn calendar(body: &str) -> String {
+        format!("BEGIN:VCALENDAR\r\n{body}END:VCALENDAR\r\n")
+    }
+
+    fn vevent(dtstart: &str, summary: &str) -> String {
+        format!("BEGIN:VEVENT\r\n{dtstart}\r\n{summary}\r\nEND:VEVENT\r\n")
+    }
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn folded_summary_is_joined() {
+        let document = calendar(&vevent(
+            "DTSTART;VALUE=DATE:20260105",
+            "SUMMARY:Abfuhr grüne\r\n  Biotonne",
+        ));
+        let (events, report) = extract_ics_event(&document, &WINDOW);
+        assert_eq!(report.events, 1);
+        assert_eq!(events[0].dtstart, Some(date(2026, Month::January, 5)));
+        assert_eq!(events[0].event_type, Some(Event::Bio));
+    }
+
+    #[test]
+    fn quoted_printable_soft_break_is_joined() {
+        let document = calendar(&vevent(
+            "DTSTART;VALUE=DATE:20260105",
+            "SUMMARY;ENCODING=QUOTED-PRINTABLE;CHARSET=UTF-8:Abfuhr schwarze Restm=C3=BCll=\r\ntonne",
+        ));
+        let (events, report) = extract_ics_event(&document, &WINDOW);
+        assert_eq!(report.skipped_lines, 0);
+        assert_eq!(events[0].event_type, Some(Event::Restmüll));
+    }
+
+    #[test]
+    fn quoted_printable_soft_break_ignores_case() {
+        let document = calendar(&vevent(
+            "DTSTART;VALUE=DATE:20260105",
+            "SUMMARY;encoding=quoted-printable:Abfuhr Laubs=C3=A4=\r\ncke",
+        ));
+        let (events, _) = extract_ics_event(&document, &WINDOW);
+        assert_eq!(events[0].event_type, Some(Event::Laubsack));
+    }
+
+    #[test]
+    fn date_time_with_tzid_is_converted_to_local_date() {
+        let timezone = "BEGIN:VTIMEZONE\r\n\
+                        TZID:Test/Zone\r\n\
+                        BEGIN:STANDARD\r\n\
+                        DTSTART:19700101T000000\r\n\
+                        TZOFFSETFROM:-0500\r\n\
+                        TZOFFSETTO:-0500\r\n\
+                        END:STANDARD\r\n\
+                        END:VTIMEZONE\r\n";
+        let event = vevent(
+            "DTSTART;TZID=Test/Zone:20260105T233000",
+            "SUMMARY:Abfuhr blaue Papiertonne",
+        );
+        let (events, _) = extract_ics_event(&calendar(&format!("{timezone}{event}")), &WINDOW);
+        // 23:30 at -05:00 is 05:30 the next morning in Germany.
+        assert_eq!(events[0].dtstart, Some(date(2026, Month::January, 6)));
+    }
+
+    #[test]
+    fn nearest_events_are_kept_beyond_max_events() {
+        let body: String = ["20260107", "20260105", "20260106"]
+            .iter()
+            .map(|day| {
+                vevent(
+                    &format!("DTSTART;VALUE=DATE:{day}"),
+                    "SUMMARY:Abfuhr Laubsäcke",
+                )
+            })
+            .collect();
+        let window = EventWindow {
+            max_events: 2,
+            ..WINDOW
+        };
+        let (events, report) = extract_ics_event(&calendar(&body), &window);
+        assert_eq!(report.evicted, 1);
+        let mut dates: Vec<_> = events.iter().filter_map(|event| event.dtstart).collect();
+        dates.sort();
+        assert_eq!(
+            dates,
+            [date(2026, Month::January, 5), date(2026, Month::January, 6)]
+        );
+    }
+
+    #[test]
+    fn dtstart_beyond_supported_dates_is_invalid() {
+        let document = calendar(&vevent(
+            "DTSTART:99991231T233000Z",
+            "SUMMARY:Abfuhr Laubsäcke",
+        ));
+        let (events, report) = extract_ics_event(&document, &WINDOW);
+        assert!(events.is_empty());
+        assert_eq!(report.invalid_dtstarts, 1);
+        assert_eq!(report.dropped_events, 1);
+    }
+
+    fn observance(start: &str, from: &str, to: &str, rrule: &str) -> Observance {
+        Observance {
+            start: parse_date_time(start).unwrap().0,
+            offset_from: parse_utc_offset(from).unwrap(),
+            offset_to: parse_utc_offset(to).unwrap(),
+            rule: YearlyRule::parse(rrule),
+        }
+    }
+
+    #[test]
+    fn observance_rules_end_at_until_and_count() {
+        let july = |year| {
+            Date::from_calendar_date(year, Month::July, 1)
+                .unwrap()
+                .midnight()
+        };
+        // Summer time from 1996 through 2009 only.
+        for daylight_rule in [
+            "FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU;UNTIL=20090329T010000Z",
+            "FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU;COUNT=14",
+        ] {
+            let timezone = TimeZone {
+                tzid: String::from("Test/Zone"),
+                observances: alloc::vec![
+                    observance("19960331T020000", "+0100", "+0200", daylight_rule),
+                    observance(
+                        "19961027T030000",
+                        "+0200",
+                        "+0100",
+                        "FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU"
+                    ),
+                ],
+            };
+            assert_eq!(timezone.offset_at(july(2009)), Some(7200));
+            assert_eq!(timezone.offset_at(july(2010)), Some(3600));
+        }
+    }
+}
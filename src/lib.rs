@@ -1 +1,31 @@
 #![no_std]
+
+extern crate alloc;
+
+pub mod ack;
+pub mod boot;
+pub mod clock;
+pub mod config;
+pub mod cron;
+pub mod device;
+pub mod diagnostics;
+pub mod dns;
+pub mod entropy;
+pub mod event;
+pub mod fetch;
+pub mod flag;
+pub mod heap;
+pub mod holidays;
+pub mod ics;
+pub mod netstats;
+pub mod ping;
+pub mod profile;
+pub mod reminder;
+pub mod scan;
+pub mod schedule;
+pub mod status_led;
+pub mod syslog;
+pub mod text;
+pub mod traffic;
+pub mod upcoming;
+pub mod version;
@@ -0,0 +1,53 @@
+//! Counters for network events.
+//!
+//! embassy-net does not expose smoltcp's packet counters, so these count what
+//! the firmware itself observes: link and DHCP transitions, WiFi connection
+//! attempts and socket-level failures of its own requests.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+#[derive(Copy, Clone)]
+pub enum Counter {
+    LinkUp,
+    LinkDown,
+    DhcpLease,
+    DhcpLost,
+    WifiDisconnect,
+    WifiConnectFailure,
+    SocketError,
+    RadioRestart,
+}
+
+const COUNTERS: usize = 8;
+
+static VALUES: [AtomicU32; COUNTERS] = [const { AtomicU32::new(0) }; COUNTERS];
+
+pub fn increment(counter: Counter) {
+    VALUES[counter as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+#[derive(defmt::Format, Debug, Clone, Copy)]
+pub struct NetStats {
+    pub link_up: u32,
+    pub link_down: u32,
+    pub dhcp_leases: u32,
+    pub dhcp_lost: u32,
+    pub wifi_disconnects: u32,
+    pub wifi_connect_failures: u32,
+    pub socket_errors: u32,
+    pub radio_restarts: u32,
+}
+
+pub fn snapshot() -> NetStats {
+    let get = |counter: Counter| VALUES[counter as usize].load(Ordering::Relaxed);
+    NetStats {
+        link_up: get(Counter::LinkUp),
+        link_down: get(Counter::LinkDown),
+        dhcp_leases: get(Counter::DhcpLease),
+        dhcp_lost: get(Counter::DhcpLost),
+        wifi_disconnects: get(Counter::WifiDisconnect),
+        wifi_connect_failures: get(Counter::WifiConnectFailure),
+        socket_errors: get(Counter::SocketError),
+        radio_restarts: get(Counter::RadioRestart),
+    }
+}
@@ -0,0 +1,80 @@
+//! ICMP echo ("ping") for diagnosing failed fetches.
+//!
+//! Pinging the gateway and a host on the internet tells apart a broken LAN
+//! or WiFi link, a missing upstream connection and a backend that is down
+//! while the internet works.
+
+use embassy_net::icmp::{IcmpEndpoint, IcmpSocket, PacketMetadata};
+use embassy_net::{IpAddress, Ipv4Address, Stack};
+use embassy_time::{Duration, Instant, with_timeout};
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::wire::{Icmpv4Packet, Icmpv4Repr};
+
+const IDENT: u16 = 0x4D52;
+const PAYLOAD: &[u8] = b"muellabfuhr";
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub enum PingError {
+    Socket,
+    Timeout,
+}
+
+/// Sends one echo request to `address` and returns the round-trip time.
+pub async fn ping(stack: Stack<'_>, address: Ipv4Address) -> Result<Duration, PingError> {
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buf = [0u8; 64];
+    let mut tx_buf = [0u8; 64];
+    let mut socket = IcmpSocket::new(stack, &mut rx_meta, &mut rx_buf, &mut tx_meta, &mut tx_buf);
+    socket
+        .bind(IcmpEndpoint::Ident(IDENT))
+        .map_err(|_| PingError::Socket)?;
+
+    let seq_no = Instant::now().as_ticks() as u16;
+    let request = Icmpv4Repr::EchoRequest {
+        ident: IDENT,
+        seq_no,
+        data: PAYLOAD,
+    };
+    let checksums = ChecksumCapabilities::default();
+    let mut message = [0u8; 64];
+    let len = request.buffer_len();
+    request.emit(
+        &mut Icmpv4Packet::new_unchecked(&mut message[..len]),
+        &checksums,
+    );
+
+    let sent_at = Instant::now();
+    socket
+        .send_to(&message[..len], IpAddress::Ipv4(address))
+        .await
+        .map_err(|_| PingError::Socket)?;
+
+    let reply = async {
+        loop {
+            let (len, from) = socket
+                .recv_from(&mut message)
+                .await
+                .map_err(|_| PingError::Socket)?;
+            if from != IpAddress::Ipv4(address) {
+                continue;
+            }
+            let Ok(packet) = Icmpv4Packet::new_checked(&message[..len]) else {
+                continue;
+            };
+            if let Ok(Icmpv4Repr::EchoReply {
+                ident: IDENT,
+                seq_no: reply_seq_no,
+                ..
+            }) = Icmpv4Repr::parse(&packet, &checksums)
+                && reply_seq_no == seq_no
+            {
+                return Ok(sent_at.elapsed());
+            }
+        }
+    };
+    with_timeout(TIMEOUT, reply)
+        .await
+        .map_err(|_| PingError::Timeout)?
+}
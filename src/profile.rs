@@ -0,0 +1,66 @@
+//! Optional power profiling of the refresh cycle.
+//!
+//! Once a marker pin is installed, every [`Span`] drives it high for the
+//! duration of its phase and logs how long the phase took. A current probe or
+//! scope on the pin can then attribute the energy of a cycle to its phases.
+//! Without a marker pin, spans do nothing.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embassy_time::Instant;
+use esp_hal::gpio::Output;
+
+#[derive(defmt::Format, Copy, Clone, Debug)]
+pub enum Phase {
+    /// From starting the WiFi controller until the connection is up.
+    RadioOn,
+    /// TCP connect and TLS handshake with the calendar backend.
+    TlsHandshake,
+    /// Sending the request and reading the response body.
+    Fetch,
+    Parse,
+}
+
+static MARKER: Mutex<RefCell<Option<Output<'static>>>> = Mutex::new(RefCell::new(None));
+
+/// Enables profiling with `marker` as the phase marker pin.
+pub fn install(mut marker: Output<'static>) {
+    marker.set_low();
+    critical_section::with(|cs| MARKER.borrow_ref_mut(cs).replace(marker));
+}
+
+fn set_marker(high: bool) -> bool {
+    critical_section::with(|cs| match MARKER.borrow_ref_mut(cs).as_mut() {
+        Some(marker) => {
+            marker.set_level(high.into());
+            true
+        }
+        None => false,
+    })
+}
+
+/// A running phase; it ends when dropped.
+#[must_use]
+pub struct Span {
+    phase: Phase,
+    started: Option<Instant>,
+}
+
+pub fn begin(phase: Phase) -> Span {
+    let started = set_marker(true).then(Instant::now);
+    Span { phase, started }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(started) = self.started {
+            set_marker(false);
+            defmt::info!(
+                "Phase {} took {} ms",
+                self.phase,
+                started.elapsed().as_millis()
+            );
+        }
+    }
+}
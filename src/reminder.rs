@@ -0,0 +1,83 @@
+//! Reminder texts: templates with placeholders and per-language bin labels.
+
+use core::fmt::Write as _;
+
+use crate::event::Event;
+use crate::text::{Overflow, Text};
+
+/// Room for a reminder with a handful of bins and both notes.
+pub const REMINDER_TEXT_LEN: usize = 192;
+
+/// A rendered reminder. It lives on the stack, so rendering cannot fail for
+/// lack of heap when the reminder is due.
+pub type ReminderText = Text<REMINDER_TEXT_LEN>;
+
+#[derive(defmt::Format, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Language {
+    De,
+    En,
+}
+
+impl Language {
+    /// Template used when none is configured.
+    pub fn default_template(self) -> &'static str {
+        match self {
+            Language::De => "Morgen: {bins} – heute Abend rausstellen",
+            Language::En => "Tomorrow: {bins} — put them out tonight",
+        }
+    }
+
+    /// Appended to reminders whose date falls on a public holiday.
+    pub fn verify_note(self) -> &'static str {
+        match self {
+            Language::De => "(bitte prüfen – Feiertag, Termin evtl. verschoben)",
+            Language::En => "(verify — public holiday, may be shifted)",
+        }
+    }
+
+    /// Appended to reminders while the calendar data is stale.
+    pub fn stale_note(self) -> &'static str {
+        match self {
+            Language::De => "(Kalenderdaten veraltet)",
+            Language::En => "(calendar data outdated)",
+        }
+    }
+}
+
+pub fn bin_label(event: Event, language: Language) -> &'static str {
+    let style = event.style();
+    match language {
+        Language::De => style.label_de,
+        Language::En => style.label_en,
+    }
+}
+
+/// Renders `template` for the given bins.
+///
+/// Supported placeholders are `{bins}` (comma separated labels) and `{count}`.
+/// A text too long for [`ReminderText`] ends in an ellipsis.
+pub fn render(template: &str, language: Language, bins: &[Event]) -> ReminderText {
+    let mut text = ReminderText::new(Overflow::Ellipsis);
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        if let Some(after) = placeholder.strip_prefix("{bins}") {
+            for (i, bin) in bins.iter().enumerate() {
+                if i > 0 {
+                    text.push_str(", ");
+                }
+                text.push_str(bin_label(*bin, language));
+            }
+            rest = after;
+        } else if let Some(after) = placeholder.strip_prefix("{count}") {
+            let _ = write!(text, "{}", bins.len());
+            rest = after;
+        } else {
+            text.push('{');
+            rest = &placeholder[1..];
+        }
+    }
+    text.push_str(rest);
+    text
+}
@@ -0,0 +1,48 @@
+//! Results of the last WiFi scan.
+//!
+//! Kept for diagnosing poor signal at the mounting spot, and for picking an
+//! access point when several serve the configured network.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+#[derive(Debug, Clone)]
+pub struct AccessPoint {
+    pub ssid: String,
+    pub bssid: [u8; 6],
+    pub channel: u8,
+    /// Signal strength in dBm.
+    pub rssi: i8,
+}
+
+static LAST_SCAN: Mutex<CriticalSectionRawMutex, RefCell<Vec<AccessPoint>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+/// Replaces the stored results and logs them.
+pub fn record(access_points: Vec<AccessPoint>) {
+    for ap in &access_points {
+        defmt::info!(
+            "AP {} {=[u8]:02x} channel {} {} dBm",
+            ap.ssid.as_str(),
+            ap.bssid,
+            ap.channel,
+            ap.rssi
+        );
+    }
+    LAST_SCAN.lock(|last| *last.borrow_mut() = access_points);
+}
+
+/// The access point of `ssid` with the best signal in the last scan.
+pub fn strongest(ssid: &str) -> Option<AccessPoint> {
+    LAST_SCAN.lock(|last| {
+        last.borrow()
+            .iter()
+            .filter(|ap| ap.ssid == ssid)
+            .max_by_key(|ap| ap.rssi)
+            .cloned()
+    })
+}
@@ -0,0 +1,53 @@
+//! Cron-like schedules for extra calendar refreshes.
+//!
+//! A schedule has the five cron fields `minute hour day month weekday` in
+//! local time, e.g. `0 6,12 * * 1-5` for 06:00 and 12:00 on weekdays. Fields
+//! take `*`, numbers, lists, ranges and steps (`*/15`, `8-18/2`). Day of
+//! month and month must be `*`: collections follow weekly rhythms, and
+//! leaving them out keeps the search for the next time within a week.
+//! `build.rs` parses the spec with [`crate::cron`].
+
+use time::Duration;
+
+use crate::clock;
+use crate::cron::CronFields;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    spec: &'static str,
+    fields: CronFields,
+}
+
+impl Schedule {
+    /// A schedule of `fields`, parsed from `spec` by `build.rs`.
+    pub(crate) const fn new(spec: &'static str, fields: CronFields) -> Self {
+        Schedule { spec, fields }
+    }
+
+    /// The spec as configured.
+    pub fn spec(&self) -> &'static str {
+        self.spec
+    }
+
+    /// The first scheduled Unix time after `after`.
+    pub fn next_after(&self, after: i64) -> Option<i64> {
+        let today = clock::local_date(after);
+        // DST changes can shift the first match of a day to just before
+        // `after`, so the search covers one day beyond a week.
+        for offset in 0..=8 {
+            let date = today.checked_add(Duration::days(offset))?;
+            if self.fields.weekdays & 1 << date.weekday().number_days_from_sunday() == 0 {
+                continue;
+            }
+            for hour in (0..24).filter(|&hour| self.fields.hours & 1 << hour != 0) {
+                for minute in (0..60).filter(|&minute| self.fields.minutes & 1 << minute != 0) {
+                    let time = clock::local_time_on(date, hour, minute);
+                    if time > after {
+                        return Some(time);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
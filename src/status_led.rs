@@ -0,0 +1,251 @@
+//! Blink patterns on the status LED for the device's system state.
+//!
+//! The boot sequence reports its progress with [`set_state`]; [`run`] owns the
+//! LED and loops over the pattern of the most recently reported state. While
+//! the calendar data is stale ([`set_stale`]), every pattern cycle ends with a
+//! yellow blink. An active reminder blinks the colors of the due bins; on the
+//! collection day itself they flash in quick pairs instead. Until the next
+//! pass, [`acknowledge`] ends either of them early and brings back the state
+//! from before, e.g. a failed fetch.
+//!
+//! [`show_next`] interrupts the pattern once to preview the next collection,
+//! unless a reminder is active.
+//!
+//! With the night light on ([`set_night_light`]), a healthy device glows dimly
+//! instead of going dark between heartbeats.
+
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use embassy_futures::select::{Either3, select3};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Timer};
+use smart_leds::colors::{BLACK, BLUE, CYAN, GREEN, MAGENTA, ORANGE, RED, WHITE, YELLOW};
+use smart_leds::{RGB8, SmartLedsWrite, brightness};
+
+use crate::flag;
+
+#[derive(defmt::Format, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SystemState {
+    Connecting,
+    TimeSync,
+    ClockNotSet,
+    FetchFailed,
+    CaptivePortal,
+    Healthy,
+    /// Bins due tomorrow, to be put out tonight.
+    ReminderActive,
+    /// Bins collected today.
+    CollectionToday,
+}
+
+/// One step of a blink pattern: show `color` for `millis`.
+struct Step {
+    color: RGB8,
+    millis: u64,
+}
+
+const fn step(color: RGB8, millis: u64) -> Step {
+    Step { color, millis }
+}
+
+static STATE: Signal<CriticalSectionRawMutex, SystemState> = Signal::new();
+static STALE: AtomicBool = AtomicBool::new(false);
+/// Whether the last state set shows due bins.
+static DUE: AtomicBool = AtomicBool::new(false);
+/// The last state set that doesn't show due bins, shown again once they are
+/// acknowledged.
+static BASE_STATE: Mutex<CriticalSectionRawMutex, Cell<SystemState>> =
+    Mutex::new(Cell::new(SystemState::Connecting));
+static REMINDER_COLORS: Mutex<CriticalSectionRawMutex, RefCell<Vec<RGB8>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+/// Night light brightness, 0 while it is off.
+static NIGHT_LIGHT: AtomicU8 = AtomicU8::new(0);
+static PREVIEW: Signal<CriticalSectionRawMutex, Vec<Step>> = Signal::new();
+
+const STALE_SUFFIX: &[Step] = &[step(YELLOW, 200), step(BLACK, 300)];
+/// Warm white, easier on the eyes at night than the white of the patterns.
+const NIGHT_LIGHT_COLOR: RGB8 = RGB8::new(255, 147, 41);
+/// Collections further ahead are counted as this many days.
+const MAX_COUNTED_DAYS: i64 = 7;
+
+pub fn set_state(state: SystemState) {
+    let due = matches!(
+        state,
+        SystemState::ReminderActive | SystemState::CollectionToday
+    );
+    if !due {
+        BASE_STATE.lock(|base| base.set(state));
+    }
+    if DUE.swap(due, Ordering::Relaxed) != due {
+        flag::set_raised(due);
+    }
+    STATE.signal(state);
+}
+
+/// Whether the reminder or collection day indicator is shown.
+pub fn due() -> bool {
+    DUE.load(Ordering::Relaxed)
+}
+
+/// Like [`set_state`], but while due bins are shown, `state` only takes
+/// effect once they are acknowledged.
+pub fn set_state_behind_due(state: SystemState) {
+    if due() {
+        BASE_STATE.lock(|base| base.set(state));
+    } else {
+        set_state(state);
+    }
+}
+
+/// Ends the reminder or collection day indicator, if one is shown, and goes
+/// back to the state from before it. Returns whether there was one.
+pub fn acknowledge() -> bool {
+    let due = DUE.load(Ordering::Relaxed);
+    if due {
+        set_state(BASE_STATE.lock(|base| base.get()));
+    }
+    due
+}
+
+pub fn set_stale(stale: bool) {
+    STALE.store(stale, Ordering::Relaxed);
+}
+
+/// Switches the night light on at `brightness`, or off with 0.
+pub fn set_night_light(brightness: u8) {
+    NIGHT_LIGHT.store(brightness, Ordering::Relaxed);
+}
+
+/// Enters [`SystemState::ReminderActive`], cycling through `colors`, one per
+/// bin that is due.
+pub fn set_reminder(colors: &[RGB8]) {
+    REMINDER_COLORS.lock(|reminder| *reminder.borrow_mut() = colors.to_vec());
+    set_state(SystemState::ReminderActive);
+}
+
+/// Enters [`SystemState::CollectionToday`] with the colors of the bins
+/// collected today.
+pub fn set_collection_today(colors: &[RGB8]) {
+    REMINDER_COLORS.lock(|reminder| *reminder.borrow_mut() = colors.to_vec());
+    set_state(SystemState::CollectionToday);
+}
+
+/// Previews the next collection: the colors of its bins, then one short white
+/// blink per day until it, up to [`MAX_COUNTED_DAYS`].
+pub fn show_next(colors: &[RGB8], days: i64) {
+    let mut steps = Vec::new();
+    steps.push(step(BLACK, 500));
+    for &color in colors {
+        steps.push(step(color, 700));
+        steps.push(step(BLACK, 300));
+    }
+    steps.push(step(BLACK, 700));
+    for _ in 0..days.clamp(0, MAX_COUNTED_DAYS) {
+        steps.push(step(WHITE, 150));
+        steps.push(step(BLACK, 350));
+    }
+    PREVIEW.signal(steps);
+}
+
+fn reminder_pattern(state: SystemState) -> Vec<Step> {
+    REMINDER_COLORS.lock(|colors| {
+        let colors = colors.borrow();
+        match state {
+            SystemState::CollectionToday => colors
+                .iter()
+                .flat_map(|&color| {
+                    [
+                        step(color, 150),
+                        step(BLACK, 150),
+                        step(color, 150),
+                        step(BLACK, 550),
+                    ]
+                })
+                .collect(),
+            _ => colors
+                .iter()
+                .flat_map(|&color| [step(color, 700), step(BLACK, 300)])
+                .collect(),
+        }
+    })
+}
+
+fn pattern(state: SystemState) -> &'static [Step] {
+    match state {
+        SystemState::Connecting => &[step(BLUE, 200), step(BLACK, 200)],
+        SystemState::TimeSync => &[
+            step(CYAN, 150),
+            step(BLACK, 150),
+            step(CYAN, 150),
+            step(BLACK, 800),
+        ],
+        SystemState::ClockNotSet => &[step(WHITE, 1000), step(BLACK, 1000)],
+        SystemState::FetchFailed => &[step(RED, 1000), step(BLACK, 1000)],
+        SystemState::CaptivePortal => &[step(MAGENTA, 500), step(BLACK, 500)],
+        // Short heartbeat so a healthy unit is distinguishable from a dead one.
+        SystemState::Healthy => &[step(GREEN, 100), step(BLACK, 4900)],
+        SystemState::ReminderActive | SystemState::CollectionToday => &[step(ORANGE, 1000)],
+    }
+}
+
+/// Drives `led` forever, switching pattern as soon as a new state is set.
+pub async fn run<L>(led: &mut L, level: u8) -> !
+where
+    L: SmartLedsWrite<Color = RGB8>,
+{
+    let mut state = SystemState::Connecting;
+    'pattern: loop {
+        let reminder = match state {
+            SystemState::ReminderActive | SystemState::CollectionToday => reminder_pattern(state),
+            _ => Vec::new(),
+        };
+        let steps: &[Step] = if reminder.is_empty() {
+            pattern(state)
+        } else {
+            &reminder
+        };
+        let suffix = if STALE.load(Ordering::Relaxed) {
+            STALE_SUFFIX
+        } else {
+            &[]
+        };
+        let night_light = match state {
+            SystemState::Healthy => NIGHT_LIGHT.load(Ordering::Relaxed),
+            _ => 0,
+        };
+        for step in steps.iter().chain(suffix) {
+            let (color, level) = if step.color == BLACK && night_light > 0 {
+                (NIGHT_LIGHT_COLOR, night_light)
+            } else {
+                (step.color, level)
+            };
+            // A failed write only costs one frame; the next step retries.
+            let _ = led.write(brightness([color].into_iter(), level));
+            let timeout = Timer::after(Duration::from_millis(step.millis));
+            match select3(timeout, STATE.wait(), PREVIEW.wait()).await {
+                Either3::First(()) => {}
+                Either3::Second(new_state) => {
+                    defmt::info!("System state: {} -> {}", state, new_state);
+                    state = new_state;
+                    continue 'pattern;
+                }
+                Either3::Third(preview) => {
+                    // A state set during the preview stays signalled and is
+                    // picked up right after it.
+                    if state != SystemState::ReminderActive {
+                        for step in &preview {
+                            let _ = led.write(brightness([step.color].into_iter(), level));
+                            Timer::after(Duration::from_millis(step.millis)).await;
+                        }
+                    }
+                    continue 'pattern;
+                }
+            }
+        }
+    }
+}
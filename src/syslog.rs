@@ -0,0 +1,213 @@
+//! Minimal RFC 5424 syslog client for audit events.
+//!
+//! Audit messages are sent as single UDP datagrams to a collector and are kept
+//! separate from the defmt debug log: they describe what the device did (fetch
+//! results, reminders, acknowledgements), not how it did it.
+//!
+//! Messages are formatted when [`queue`]d and sent by [`run`] on its own
+//! socket, so a slow network never holds up the caller. They are formatted
+//! into fixed-size buffers, so auditing a reminder never needs the heap.
+//!
+//! Unknown SUMMARY lines are reported as well, so the category names of new
+//! municipalities can be collected from the field and added to the mapping.
+
+use core::fmt::Write as _;
+
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpEndpoint, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use time::UtcDateTime;
+
+use crate::config::CONFIG;
+use crate::device;
+use crate::netstats::{self, Counter};
+use crate::text::{Overflow, Text};
+
+pub const SYSLOG_PORT: u16 = 514;
+/// Messages waiting to be sent. A pass audits only a handful of events.
+const QUEUE_DEPTH: usize = 8;
+
+const FACILITY_LOCAL0: u8 = 16;
+const APP_NAME: &str = "muellabfuhr-reminder";
+// 32473 is the private enterprise number reserved for documentation (RFC 5612).
+const SD_ID: &str = "audit@32473";
+/// Fits the send buffer of [`run`].
+const MAX_MESSAGE_LEN: usize = 480;
+/// Longer parameter values are cut, so the header and the closing bracket
+/// of the structured data always fit a message.
+const MAX_PARAM_LEN: usize = 256;
+
+pub type Message = Text<MAX_MESSAGE_LEN>;
+
+static QUEUE: Channel<CriticalSectionRawMutex, Message, QUEUE_DEPTH> = Channel::new();
+
+#[derive(Copy, Clone)]
+#[repr(u8)]
+enum Severity {
+    Warning = 4,
+    Notice = 5,
+}
+
+#[derive(Debug)]
+pub enum AuditEvent<'a> {
+    FetchOk {
+        events: usize,
+        dropped: usize,
+    },
+    FetchFailed {
+        reason: &'a str,
+    },
+    ReminderFired {
+        event: &'a str,
+        stale: bool,
+    },
+    TestReminder {
+        event: &'a str,
+    },
+    UnknownSummary {
+        summary: &'a str,
+    },
+    /// A shown reminder was acknowledged, by `source` such as the button.
+    Acknowledged {
+        source: &'a str,
+    },
+}
+
+impl AuditEvent<'_> {
+    fn msg_id(&self) -> &'static str {
+        match self {
+            AuditEvent::FetchOk { .. } => "FETCH_OK",
+            AuditEvent::FetchFailed { .. } => "FETCH_FAIL",
+            AuditEvent::ReminderFired { .. } => "REMINDER",
+            AuditEvent::TestReminder { .. } => "REMINDER_TEST",
+            AuditEvent::UnknownSummary { .. } => "UNKNOWN_SUMMARY",
+            AuditEvent::Acknowledged { .. } => "ACKNOWLEDGED",
+        }
+    }
+
+    fn severity(&self) -> Severity {
+        match self {
+            AuditEvent::FetchFailed { .. } => Severity::Warning,
+            _ => Severity::Notice,
+        }
+    }
+}
+
+/// Appends `value` as an SD-PARAM value, escaping `"`, `\` and `]`, and
+/// cut to [`MAX_PARAM_LEN`].
+fn push_param_value(msg: &mut Message, value: &str) {
+    let mut escaped = Text::<MAX_PARAM_LEN>::new(Overflow::Ellipsis);
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    msg.push_str(escaped.as_str());
+}
+
+/// Formats `event` as an RFC 5424 message. Without a synchronized clock the
+/// timestamp is the NILVALUE `-`.
+pub fn format_message(unix_time: Option<i64>, event: &AuditEvent<'_>) -> Message {
+    let mut msg = Message::new(Overflow::Ellipsis);
+    let pri = FACILITY_LOCAL0 * 8 + event.severity() as u8;
+    let _ = write!(msg, "<{}>1 ", pri);
+
+    match unix_time.and_then(|t| UtcDateTime::from_unix_timestamp(t).ok()) {
+        Some(ts) => {
+            let _ = write!(
+                msg,
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                ts.year(),
+                ts.month() as u8,
+                ts.day(),
+                ts.hour(),
+                ts.minute(),
+                ts.second()
+            );
+        }
+        None => msg.push('-'),
+    }
+
+    let _ = write!(
+        msg,
+        " {} {} - {} [{}",
+        device::hostname(),
+        APP_NAME,
+        event.msg_id(),
+        SD_ID
+    );
+    match event {
+        AuditEvent::FetchOk { events, dropped } => {
+            let _ = write!(
+                msg,
+                " events=\"{}\" dropped=\"{}\"] calendar fetched",
+                events, dropped
+            );
+        }
+        AuditEvent::FetchFailed { reason } => {
+            msg.push_str(" reason=\"");
+            push_param_value(&mut msg, reason);
+            msg.push_str("\"] calendar fetch failed");
+        }
+        AuditEvent::ReminderFired { event, stale } => {
+            msg.push_str(" event=\"");
+            push_param_value(&mut msg, event);
+            let _ = write!(msg, "\" stale=\"{}\"] reminder fired", stale);
+        }
+        AuditEvent::TestReminder { event } => {
+            msg.push_str(" event=\"");
+            push_param_value(&mut msg, event);
+            msg.push_str("\"] test reminder fired");
+        }
+        AuditEvent::UnknownSummary { summary } => {
+            msg.push_str(" summary=\"");
+            push_param_value(&mut msg, summary);
+            msg.push_str("\"] unknown event summary");
+        }
+        AuditEvent::Acknowledged { source } => {
+            msg.push_str(" source=\"");
+            push_param_value(&mut msg, source);
+            msg.push_str("\"] reminder acknowledged");
+        }
+    }
+    msg
+}
+
+/// The audit queue is full, the message was dropped.
+#[derive(Debug)]
+pub struct QueueFull;
+
+/// Queues `event` for [`run`]. Fails if the queue is full.
+pub fn queue(unix_time: Option<i64>, event: &AuditEvent<'_>) -> Result<(), QueueFull> {
+    QUEUE
+        .try_send(format_message(unix_time, event))
+        .map_err(|_| QueueFull)
+}
+
+/// Queues `event` if a syslog collector is configured. A full queue drops
+/// the message; auditing must never hold up the reminder logic.
+pub fn audit(unix_time: Option<i64>, event: &AuditEvent<'_>) {
+    if CONFIG.syslog_host.is_some() && queue(unix_time, event).is_err() {
+        defmt::warn!("Audit queue full, dropping {}", event.msg_id());
+    }
+}
+
+/// Sends the queued messages to `collector` forever.
+pub async fn run(stack: Stack<'_>, collector: IpEndpoint) -> ! {
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buf = [0u8; 64];
+    let mut tx_buf = [0u8; 512];
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buf, &mut tx_meta, &mut tx_buf);
+    socket.bind(0).expect("Failed to bind the syslog socket");
+
+    loop {
+        let msg = QUEUE.receive().await;
+        if socket.send_to(msg.as_bytes(), collector).await.is_err() {
+            defmt::warn!("Failed to send audit message");
+            netstats::increment(Counter::SocketError);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Fixed-size text buffers for messages built when the heap may be short.
+//!
+//! A [`Text`] never allocates. Text that does not fit is truncated at a
+//! character boundary, according to its [`Overflow`] policy, and everything
+//! pushed after that is dropped. `write!` into a `Text` therefore never
+//! fails.
+
+use core::fmt;
+
+const ELLIPSIS: &str = "…";
+
+/// What a full [`Text`] does with the rest.
+#[derive(defmt::Format, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Drop what does not fit.
+    Cut,
+    /// End with an ellipsis, making room for it if needed.
+    Ellipsis,
+}
+
+pub struct Text<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    overflow: Overflow,
+    truncated: bool,
+}
+
+/// The largest character boundary of `s` at or before `index`.
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    (0..=index.min(s.len()))
+        .rev()
+        .find(|&i| s.is_char_boundary(i))
+        .unwrap_or(0)
+}
+
+impl<const N: usize> Text<N> {
+    pub const fn new(overflow: Overflow) -> Self {
+        const { assert!(N >= ELLIPSIS.len()) };
+        Text {
+            buf: [0; N],
+            len: 0,
+            overflow,
+            truncated: false,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole characters are ever copied in.
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+    }
+
+    /// Whether anything was dropped.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn append(&mut self, s: &str) {
+        self.buf[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+        self.len += s.len();
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        if self.truncated {
+            return;
+        }
+        if self.len + s.len() <= N {
+            self.append(s);
+            return;
+        }
+        self.truncated = true;
+        let limit = match self.overflow {
+            Overflow::Cut => N,
+            Overflow::Ellipsis => N - ELLIPSIS.len(),
+        };
+        if self.len > limit {
+            self.len = floor_char_boundary(self.as_str(), limit);
+        } else {
+            let fits = floor_char_boundary(s, limit - self.len);
+            self.append(&s[..fits]);
+        }
+        if self.overflow == Overflow::Ellipsis {
+            self.append(ELLIPSIS);
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+}
+
+impl<const N: usize> fmt::Write for Text<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Display for Text<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> defmt::Format for Text<N> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=str}", self.as_str())
+    }
+}
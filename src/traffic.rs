@@ -0,0 +1,42 @@
+//! Download accounting per day and month, for deployments on metered
+//! uplinks such as LTE hotspots.
+//!
+//! Counts response bodies only; headers and TLS overhead add a few kB per
+//! fetch on top.
+
+use time::Date;
+
+#[derive(Default)]
+pub struct Traffic {
+    date: Option<Date>,
+    today: u64,
+    month: u64,
+}
+
+impl Traffic {
+    /// Starts new periods when `date` is a new day or month.
+    pub fn roll(&mut self, date: Date) {
+        if let Some(last) = self.date {
+            if (last.year(), last.month()) != (date.year(), date.month()) {
+                self.month = 0;
+            }
+            if last != date {
+                self.today = 0;
+            }
+        }
+        self.date = Some(date);
+    }
+
+    pub fn add(&mut self, bytes: usize) {
+        self.today += bytes as u64;
+        self.month += bytes as u64;
+    }
+
+    pub fn today(&self) -> u64 {
+        self.today
+    }
+
+    pub fn month(&self) -> u64 {
+        self.month
+    }
+}
@@ -0,0 +1,58 @@
+//! The next known collection, announced on demand.
+//!
+//! The daily pass records it with [`update`]; [`announce`] can then be called
+//! at any time, e.g. from a button, and counts the days against the clock at
+//! that moment rather than at the last pass.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use time::Date;
+
+use crate::clock::WallClock;
+use crate::event::Event;
+use crate::ics::{self, IcsEvent};
+use crate::status_led;
+
+struct Upcoming {
+    clock: WallClock,
+    date: Date,
+    bins: Vec<Event>,
+}
+
+static NEXT: Mutex<CriticalSectionRawMutex, RefCell<Option<Upcoming>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Records the earliest collection in `events` from today on.
+pub fn update(clock: WallClock, events: &[IcsEvent]) {
+    let today = clock.today();
+    let date = events
+        .iter()
+        .filter(|event| event.event_type.is_some())
+        .filter_map(|event| event.dtstart)
+        .filter(|&date| date >= today)
+        .min();
+    let upcoming = date.map(|date| Upcoming {
+        clock,
+        date,
+        bins: ics::bins_on(events, date),
+    });
+    NEXT.lock(|next| *next.borrow_mut() = upcoming);
+}
+
+/// Logs the next collection and previews it on the status LED.
+pub fn announce() {
+    NEXT.lock(|next| match next.borrow().as_ref() {
+        Some(upcoming) => {
+            let days = (upcoming.date - upcoming.clock.today()).whole_days();
+            for bin in &upcoming.bins {
+                defmt::info!("Next collection: {} in {} days", bin, days);
+            }
+            let colors: Vec<_> = upcoming.bins.iter().map(|bin| bin.style().color).collect();
+            status_led::show_next(&colors, days);
+        }
+        None => defmt::info!("No upcoming collection known"),
+    });
+}
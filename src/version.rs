@@ -0,0 +1,9 @@
+//! What exactly a deployed unit runs, embedded by `build.rs`.
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// `git describe` of the source tree, with `-dirty` for uncommitted changes.
+pub const GIT_HASH: &str = env!("GIT_HASH");
+/// UTC build time, or `SOURCE_DATE_EPOCH` if set.
+pub const BUILD_TIME: &str = env!("BUILD_TIME");
+/// Comma-separated cargo features the image was built with.
+pub const ENABLED_FEATURES: &str = env!("ENABLED_FEATURES");